@@ -0,0 +1,39 @@
+//! CPU trace generation over [workloads::arithmetic_loop] executions
+//! padding to `2^10`, `2^12` and `2^14` rows
+//!
+//! No logger is installed, so the tables are not rendered to the debug
+//! log on every iteration and only their generation is timed.
+
+use criterion::{
+    criterion_group,
+    criterion_main,
+    BenchmarkId,
+    Criterion,
+};
+use pixie_zkvm::workloads;
+
+fn cpu_trace_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cpu_trace_generation");
+    for log_rows in [10, 12, 14] {
+        let rows = 1 << log_rows;
+        let program =
+            workloads::arithmetic_loop(workloads::iterations_for_rows(rows));
+
+        // Simulation is timed separately, only once per size
+        let timing = workloads::time_trace_generation(&program, rows)
+            .expect("workload halts within its rows");
+        timing.print();
+
+        let simulation = workloads::simulate(&program, rows)
+            .expect("workload halts within its rows");
+        group.bench_with_input(
+            BenchmarkId::from_parameter(rows),
+            &simulation,
+            |b, simulation| b.iter(|| workloads::cpu_trace(simulation)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cpu_trace_generation);
+criterion_main!(benches);
@@ -0,0 +1,596 @@
+//! A line based assembly syntax for [Program]s, so that they need not be
+//! built by hand. Every line holds at most one instruction or directive,
+//! anything after a `;` is a comment:
+//!
+//! ```text
+//! .entry 0x00            ; program counter to start from, `0` by default
+//! .data 0x40 = 0x20      ; initial value of memory at `0x40`
+//! .readable 0x40..=0x41  ; addresses loads may access, all by default
+//! .writable 0x42..=0x42  ; addresses stores may access, all by default
+//!
+//!     lb r0, [0x40]      ; memory locations go in brackets
+//!     add r0, r1
+//!     jz r0, 0x02        ; jump targets are instruction locations
+//!     lis r1, -1
+//!     halt
+//! ```
+//!
+//! Instructions are laid out one after the other from program counter `0`.
+//! Numbers are decimal, or hexadecimal / binary with a `0x` / `0b` prefix.
+//! [disassemble] goes the other way, rendering a [Program] in this syntax.
+
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+};
+
+use anyhow::{
+    anyhow,
+    Result,
+};
+
+use crate::vm_specs::{
+    Instruction,
+    InstructionLocation,
+    MemoryConfig,
+    MemoryLocation,
+    Program,
+    Register,
+    Word,
+    REGISTER_NAMES,
+};
+
+/// Directives which may appear at most once, applied to the [Program]
+/// once every line is read
+#[derive(Default)]
+struct Directives {
+    entry_point: Option<u8>,
+    readable: Option<RangeInclusive<Word>>,
+    writable: Option<RangeInclusive<Word>>,
+}
+
+/// Parses the assembly `src` into a [Program]. Errors point at the first
+/// offending line.
+pub fn assemble(src: &str) -> Result<Program> {
+    let mut program = Program {
+        entry_point: 0,
+        code: HashMap::new(),
+        memory_init: HashMap::new(),
+        ..Default::default()
+    };
+    let mut directives = Directives::default();
+
+    for (idx, line) in src
+        .lines()
+        .enumerate()
+    {
+        let line = line
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        assemble_line(&mut program, &mut directives, line)
+            .map_err(|err| anyhow!("line {}: {}", idx + 1, err))?;
+    }
+
+    let default = MemoryConfig::default();
+    program.entry_point = directives
+        .entry_point
+        .unwrap_or_default();
+    program.memory_config = MemoryConfig {
+        readable: directives
+            .readable
+            .unwrap_or(default.readable),
+        writable: directives
+            .writable
+            .unwrap_or(default.writable),
+    };
+    Ok(program)
+}
+
+/// Renders `prog` as assembly: the `.entry` directive, `.data` directives
+/// sorted by address, `.readable` / `.writable` directives for the ranges
+/// of `memory_config` which are not the whole address space, then the
+/// instructions sorted by program counter, each annotated with its program
+/// counter. As [assemble] lays
+/// instructions out contiguously from `0`, programs with gaps in their
+/// `code` do not round-trip.
+pub fn disassemble(prog: &Program) -> String {
+    let mut asm = format!(".entry {:#04x}\n", prog.entry_point);
+
+    let mut memory_init = prog
+        .memory_init
+        .iter()
+        .collect::<Vec<(&Word, &Word)>>();
+    memory_init.sort();
+    for (address, value) in memory_init {
+        asm.push_str(&format!(".data {:#x} = {:#x}\n", address, value));
+    }
+
+    let default = MemoryConfig::default();
+    let ranges = [
+        (".readable", &prog.memory_config.readable, default.readable),
+        (".writable", &prog.memory_config.writable, default.writable),
+    ];
+    for (directive, range, default) in ranges {
+        if *range != default {
+            asm.push_str(&format!(
+                "{} {:#x}..={:#x}\n",
+                directive,
+                range.start(),
+                range.end()
+            ));
+        }
+    }
+
+    let mut code = prog
+        .code
+        .iter()
+        .collect::<Vec<(&u8, &Instruction)>>();
+    code.sort_by_key(|(pc, _)| **pc);
+    if !code.is_empty() {
+        asm.push('\n');
+    }
+    for (pc, instruction) in code {
+        asm.push_str(&format!(
+            "    {:<20}; {:#04x}\n",
+            disassemble_instruction(instruction),
+            pc
+        ));
+    }
+    asm
+}
+
+fn disassemble_instruction(instruction: &Instruction) -> String {
+    let reg = |reg: &Register| REGISTER_NAMES[usize::from(*reg)].to_string();
+    let (mnemonic, operands) = match instruction {
+        Instruction::Add(a, b) => ("add", vec![reg(a), reg(b)]),
+        Instruction::Sub(a, b) => ("sub", vec![reg(a), reg(b)]),
+        Instruction::Mul(a, b) => ("mul", vec![reg(a), reg(b)]),
+        Instruction::Div(a, b) => ("div", vec![reg(a), reg(b)]),
+        Instruction::Shl(r, n) => ("shl", vec![reg(r), n.to_string()]),
+        Instruction::Shr(r, n) => ("shr", vec![reg(r), n.to_string()]),
+        Instruction::And(a, b) => ("and", vec![reg(a), reg(b)]),
+        Instruction::Or(a, b) => ("or", vec![reg(a), reg(b)]),
+        Instruction::Xor(a, b) => ("xor", vec![reg(a), reg(b)]),
+        Instruction::Mov(a, b) => ("mov", vec![reg(a), reg(b)]),
+        Instruction::Slt(a, b) => ("slt", vec![reg(a), reg(b)]),
+        Instruction::Seq(a, b) => ("seq", vec![reg(a), reg(b)]),
+        Instruction::Jz(r, l) => ("jz", vec![reg(r), format!("{:#04x}", l.0)]),
+        Instruction::Jnz(r, l) => {
+            ("jnz", vec![reg(r), format!("{:#04x}", l.0)])
+        }
+        Instruction::Lb(r, l) => ("lb", vec![reg(r), format!("[{:#x}]", l.0)]),
+        Instruction::Sb(r, l) => ("sb", vec![reg(r), format!("[{:#x}]", l.0)]),
+        Instruction::Li(r, imm) => ("li", vec![reg(r), format!("{:#x}", imm)]),
+        // Signed, so that `-1` reads as such rather than as `0xff`
+        Instruction::Lis(r, imm) => ("lis", vec![reg(r), imm.to_string()]),
+        Instruction::Not(r) => ("not", vec![reg(r)]),
+        Instruction::Out(r) => ("out", vec![reg(r)]),
+        Instruction::Call(l) => ("call", vec![format!("{:#04x}", l.0)]),
+        Instruction::Ret => ("ret", vec![]),
+        Instruction::Halt => ("halt", vec![]),
+    };
+    match operands.is_empty() {
+        true => mnemonic.to_string(),
+        false => format!("{} {}", mnemonic, operands.join(", ")),
+    }
+}
+
+fn assemble_line(
+    program: &mut Program,
+    directives: &mut Directives,
+    line: &str,
+) -> Result<()> {
+    let (keyword, operands) = line
+        .split_once(char::is_whitespace)
+        .unwrap_or((line, ""));
+    let keyword = keyword.to_ascii_lowercase();
+
+    match keyword.as_str() {
+        ".entry" => {
+            if directives
+                .entry_point
+                .is_some()
+            {
+                return Err(anyhow!("duplicate `.entry` directive"));
+            }
+            directives.entry_point = Some(parse_u8(operands.trim())?);
+        }
+        ".readable" | ".writable" => {
+            let range = match keyword.as_str() {
+                ".readable" => &mut directives.readable,
+                _ => &mut directives.writable,
+            };
+            if range.is_some() {
+                return Err(anyhow!("duplicate `{}` directive", keyword));
+            }
+            *range = Some(parse_range(&keyword, operands.trim())?);
+        }
+        ".data" => {
+            let (address, value) = operands
+                .split_once('=')
+                .ok_or_else(|| {
+                    anyhow!("expected `.data <address> = <value>`")
+                })?;
+            let address = parse_word(address.trim())?;
+            let value = parse_word(value.trim())?;
+            if program
+                .memory_init
+                .insert(address, value)
+                .is_some()
+            {
+                return Err(anyhow!(
+                    "memory at {:#x} is initialized more than once",
+                    address
+                ));
+            }
+        }
+        directive if directive.starts_with('.') => {
+            return Err(anyhow!("unknown directive `{}`", directive));
+        }
+        mnemonic => {
+            let pc = u8::try_from(program.code.len()).map_err(|_| {
+                anyhow!("program exceeds {} instructions", 1 << u8::BITS)
+            })?;
+            let instruction =
+                parse_instruction(mnemonic, &split_operands(operands)?)?;
+            program
+                .code
+                .insert(pc, instruction);
+        }
+    }
+    Ok(())
+}
+
+/// Comma separated operands of an instruction, none if `operands` is blank
+fn split_operands(operands: &str) -> Result<Vec<&str>> {
+    if operands
+        .trim()
+        .is_empty()
+    {
+        return Ok(vec![]);
+    }
+    operands
+        .split(',')
+        .map(str::trim)
+        .map(|operand| match operand.is_empty() {
+            true => Err(anyhow!("empty operand")),
+            false => Ok(operand),
+        })
+        .collect()
+}
+
+fn parse_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+) -> Result<Instruction> {
+    let instruction = match mnemonic {
+        "add" => register_pair(operands, Instruction::Add)?,
+        "sub" => register_pair(operands, Instruction::Sub)?,
+        "mul" => register_pair(operands, Instruction::Mul)?,
+        "div" => register_pair(operands, Instruction::Div)?,
+        "shl" => register_and(operands, parse_u8, Instruction::Shl)?,
+        "shr" => register_and(operands, parse_u8, Instruction::Shr)?,
+        "and" => register_pair(operands, Instruction::And)?,
+        "or" => register_pair(operands, Instruction::Or)?,
+        "xor" => register_pair(operands, Instruction::Xor)?,
+        "mov" => register_pair(operands, Instruction::Mov)?,
+        "slt" => register_pair(operands, Instruction::Slt)?,
+        "seq" => register_pair(operands, Instruction::Seq)?,
+        "jz" => register_and(operands, parse_jump_target, Instruction::Jz)?,
+        "jnz" => register_and(operands, parse_jump_target, Instruction::Jnz)?,
+        "lb" => register_and(operands, parse_memory, Instruction::Lb)?,
+        "sb" => register_and(operands, parse_memory, Instruction::Sb)?,
+        "li" => register_and(operands, parse_word, Instruction::Li)?,
+        "lis" => register_and(operands, parse_i8, Instruction::Lis)?,
+        "not" => {
+            let [reg] = expect_operands(operands)?;
+            Instruction::Not(parse_register(reg)?)
+        }
+        "out" => {
+            let [reg] = expect_operands(operands)?;
+            Instruction::Out(parse_register(reg)?)
+        }
+        "call" => {
+            let [target] = expect_operands(operands)?;
+            Instruction::Call(parse_jump_target(target)?)
+        }
+        "ret" => {
+            let [] = expect_operands(operands)?;
+            Instruction::Ret
+        }
+        "halt" => {
+            let [] = expect_operands(operands)?;
+            Instruction::Halt
+        }
+        _ => return Err(anyhow!("unknown mnemonic `{}`", mnemonic)),
+    };
+    Ok(instruction)
+}
+
+fn expect_operands<'a, const N: usize>(
+    operands: &[&'a str]
+) -> Result<[&'a str; N]> {
+    operands
+        .try_into()
+        .map_err(|_| {
+            anyhow!("expected {} operand(s), found {}", N, operands.len())
+        })
+}
+
+fn register_pair(
+    operands: &[&str],
+    make: fn(Register, Register) -> Instruction,
+) -> Result<Instruction> {
+    register_and(operands, parse_register, make)
+}
+
+fn register_and<T>(
+    operands: &[&str],
+    parse: fn(&str) -> Result<T>,
+    make: fn(Register, T) -> Instruction,
+) -> Result<Instruction> {
+    let [reg, operand] = expect_operands(operands)?;
+    Ok(make(parse_register(reg)?, parse(operand)?))
+}
+
+fn parse_register(token: &str) -> Result<Register> {
+    match token
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "r0" => Ok(Register::R0),
+        "r1" => Ok(Register::R1),
+        "r2" => Ok(Register::R2),
+        "r3" => Ok(Register::R3),
+        _ => Err(anyhow!("unknown register `{}`", token)),
+    }
+}
+
+fn parse_word(token: &str) -> Result<Word> {
+    let lowercase = token.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lowercase.strip_prefix("0x") {
+        Word::from_str_radix(hex, 16)
+    } else if let Some(binary) = lowercase.strip_prefix("0b") {
+        Word::from_str_radix(binary, 2)
+    } else {
+        lowercase.parse::<Word>()
+    };
+    parsed.map_err(|_| anyhow!("invalid number `{}`", token))
+}
+
+fn parse_u8(token: &str) -> Result<u8> {
+    u8::try_from(parse_word(token)?)
+        .map_err(|_| anyhow!("`{}` does not fit in a byte", token))
+}
+
+/// A signed byte, either a negative number like `-0x80` or a non-negative
+/// one up to `127`
+fn parse_i8(token: &str) -> Result<i8> {
+    let (negative, magnitude) = match token.strip_prefix('-') {
+        Some(magnitude) => (true, magnitude),
+        None => (false, token),
+    };
+    let magnitude = i64::from(parse_word(magnitude.trim())?);
+    let value = if negative { -magnitude } else { magnitude };
+    i8::try_from(value)
+        .map_err(|_| anyhow!("`{}` does not fit in a signed byte", token))
+}
+
+/// An inclusive range of addresses, written as `0x40..=0x7f`
+fn parse_range(
+    directive: &str,
+    token: &str,
+) -> Result<RangeInclusive<Word>> {
+    let (first, last) = token
+        .split_once("..=")
+        .ok_or_else(|| {
+            anyhow!("expected `{} <first>..=<last>`", directive)
+        })?;
+    Ok(parse_word(first.trim())?..=parse_word(last.trim())?)
+}
+
+fn parse_jump_target(token: &str) -> Result<InstructionLocation> {
+    Ok(InstructionLocation(parse_u8(token)?))
+}
+
+/// A memory location, written in brackets as `[0x40]`
+fn parse_memory(token: &str) -> Result<MemoryLocation> {
+    let address = token
+        .strip_prefix('[')
+        .and_then(|token| token.strip_suffix(']'))
+        .ok_or_else(|| {
+            anyhow!(
+                "expected a memory location like `[0x40]`, found `{}`",
+                token
+            )
+        })?;
+    Ok(MemoryLocation(parse_word(address.trim())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures,
+        vm_specs::ProgramBuilder,
+    };
+
+    #[test]
+    fn test_assemble_add() {
+        let src = "
+            ; Adds two numbers from memory and stores the result at 0x42
+            .data 0x40 = 0x20
+            .data 0x41 = 0x45
+
+            lb r0, [0x40]
+            lb r1, [0x41]
+            add r0, r1
+            sb r0, [0x42]   ; result
+            halt
+        ";
+        assert_eq!(assemble(src).unwrap(), fixtures::add());
+    }
+
+    #[test]
+    fn test_assemble_operands() {
+        let src = "
+            .entry 2
+            LI R2, 0b101
+            lis r3, -0x80
+            jnz r2, 0x01
+            not r1
+            HALT
+        ";
+        let program = assemble(src).unwrap();
+        assert_eq!(program.entry_point, 2);
+        assert_eq!(program.code[&0], Instruction::Li(Register::R2, 5));
+        assert_eq!(program.code[&1], Instruction::Lis(Register::R3, -128));
+        assert_eq!(
+            program.code[&2],
+            Instruction::Jnz(Register::R2, InstructionLocation(1))
+        );
+        assert_eq!(program.code[&3], Instruction::Not(Register::R1));
+        assert_eq!(program.code[&4], Instruction::Halt);
+        assert!(program
+            .memory_init
+            .is_empty());
+    }
+
+    #[test]
+    fn test_disassemble_add() {
+        let expected = "\
+.entry 0x00
+.data 0x40 = 0x20
+.data 0x41 = 0x45
+
+    lb r0, [0x40]       ; 0x00
+    lb r1, [0x41]       ; 0x01
+    add r0, r1          ; 0x02
+    sb r0, [0x42]       ; 0x03
+    halt                ; 0x04
+";
+        assert_eq!(disassemble(&fixtures::add()), expected);
+    }
+
+    /// A program using every instruction, with edge case operands
+    fn every_instruction() -> Program {
+        let builder = ProgramBuilder::new()
+            .entry_point(0x0a)
+            .memory(0, 0)
+            .memory(0x40, Word::MAX);
+        [
+            Instruction::Add(Register::R0, Register::R1),
+            Instruction::Sub(Register::R1, Register::R2),
+            Instruction::Mul(Register::R2, Register::R3),
+            Instruction::Div(Register::R3, Register::R0),
+            Instruction::Shl(Register::R0, 0),
+            Instruction::Shr(Register::R1, u8::MAX),
+            Instruction::Jz(Register::R2, InstructionLocation(0)),
+            Instruction::Jnz(Register::R3, InstructionLocation(u8::MAX)),
+            Instruction::Lb(Register::R0, MemoryLocation(Word::MAX)),
+            Instruction::Sb(Register::R1, MemoryLocation(0)),
+            Instruction::Halt,
+            Instruction::And(Register::R2, Register::R3),
+            Instruction::Or(Register::R3, Register::R2),
+            Instruction::Xor(Register::R1, Register::R0),
+            Instruction::Mov(Register::R0, Register::R3),
+            Instruction::Slt(Register::R1, Register::R2),
+            Instruction::Seq(Register::R2, Register::R1),
+            Instruction::Out(Register::R3),
+            Instruction::Not(Register::R3),
+            Instruction::Li(Register::R2, Word::MAX),
+            Instruction::Lis(Register::R0, i8::MIN),
+            Instruction::Lis(Register::R1, -1),
+            Instruction::Lis(Register::R2, i8::MAX),
+            Instruction::Call(InstructionLocation(u8::MAX)),
+            Instruction::Ret,
+        ]
+        .into_iter()
+        .fold(builder, ProgramBuilder::instruction)
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    /// Restricted address ranges are rendered as `.readable` / `.writable`
+    /// directives, the whole address space as none
+    fn test_disassemble_memory_config() {
+        let program = Program {
+            memory_config: MemoryConfig {
+                readable: 0x40..=0x41,
+                ..Default::default()
+            },
+            ..fixtures::add()
+        };
+        let asm = disassemble(&program);
+        assert!(asm.contains(".readable 0x40..=0x41\n"));
+        assert!(!asm.contains(".writable"));
+        assert!(!disassemble(&fixtures::add()).contains(".readable"));
+    }
+
+    #[test]
+    /// `assemble(disassemble(p)) == p` over sample programs covering the
+    /// whole instruction set and restricted address ranges
+    fn test_disassemble_round_trip() {
+        let samples = [
+            fixtures::add(),
+            fixtures::simple_loop(),
+            fixtures::countdown(0),
+            fixtures::countdown(Word::MAX),
+            every_instruction(),
+            Program::default(),
+            Program {
+                memory_config: MemoryConfig {
+                    readable: 0x40..=0x41,
+                    writable: 0x42..=0x42,
+                },
+                ..fixtures::add()
+            },
+        ];
+        for program in samples {
+            let asm = disassemble(&program);
+            assert_eq!(assemble(&asm).unwrap(), program, "{}", asm);
+        }
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let cases = [
+            ("halt\nnop r0, r1", "line 2: unknown mnemonic `nop`"),
+            ("add r0, r4", "line 1: unknown register `r4`"),
+            ("add r0", "line 1: expected 2 operand(s), found 1"),
+            ("\n\nhalt r0", "line 3: expected 0 operand(s), found 1"),
+            ("lb r0, 0x40", "line 1: expected a memory location like"),
+            ("jz r0, 0x100", "line 1: `0x100` does not fit in a byte"),
+            ("lis r0, 128", "line 1: `128` does not fit in a signed byte"),
+            ("li r0, 0xzz", "line 1: invalid number `0xzz`"),
+            (".data 0x40 0x20", "line 1: expected `.data <address>"),
+            (".data 1 = 2\n.data 1 = 3", "line 2: memory at 0x1 is"),
+            (".entry 0\n.entry 1", "line 2: duplicate `.entry` directive"),
+            (".start 0", "line 1: unknown directive `.start`"),
+            (".readable 0x40", "line 1: expected `.readable <first>..=<"),
+            (".writable 0..=x", "line 1: invalid number `x`"),
+            (
+                ".writable 0..=1\n.writable 2..=3",
+                "line 2: duplicate `.writable` directive",
+            ),
+        ];
+        for (src, expected) in cases {
+            let err = assemble(src)
+                .unwrap_err()
+                .to_string();
+            assert!(
+                err.starts_with(expected),
+                "`{}` errored with `{}`",
+                src,
+                err
+            );
+        }
+    }
+}
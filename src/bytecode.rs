@@ -0,0 +1,259 @@
+//! A compact, fixed width binary format for [Program]s, for keeping them
+//! on disk. All numbers are little endian:
+//!
+//! ```text
+//! header       : magic `PXBC` | entry point (u8)
+//!              | readable start (u32) | readable end (u32)
+//!              | writable start (u32) | writable end (u32)
+//!              | instruction count (u16) | memory cell count (u32)
+//! instruction  : program counter (u8) | opcode (u8)
+//!              | operand A (u8) | operand B (u32)
+//! memory cell  : address (u32) | value (u32)
+//! ```
+//!
+//! Address ranges are inclusive, see [MemoryConfig]. The header is
+//! followed by the instructions sorted by program counter,
+//! then by the memory cells sorted by address. Opcodes and operands are
+//! those of [Instruction::get_opcode] and [Instruction::get_operands], so
+//! a [Program] encodes to exactly one byte string.
+
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+};
+
+use anyhow::{
+    anyhow,
+    Context,
+    Result,
+};
+
+use crate::vm_specs::{
+    Instruction,
+    MemoryConfig,
+    Program,
+    Word,
+};
+
+/// Leading bytes of every encoded [Program]
+pub const MAGIC: [u8; 4] = *b"PXBC";
+
+const HEADER_BYTES: usize = MAGIC.len() + 1 + 4 * 4 + 2 + 4;
+const INSTRUCTION_BYTES: usize = 1 + 1 + 1 + 4;
+const MEMORY_CELL_BYTES: usize = 4 + 4;
+
+/// Encodes `prog`, see the module documentation for the layout
+pub fn to_bytes(prog: &Program) -> Vec<u8> {
+    let mut code = prog
+        .code
+        .iter()
+        .collect::<Vec<(&u8, &Instruction)>>();
+    code.sort_by_key(|&(pc, _)| *pc);
+    let mut memory = prog
+        .memory_init
+        .iter()
+        .collect::<Vec<(&Word, &Word)>>();
+    memory.sort();
+
+    let mut bytes = Vec::with_capacity(
+        HEADER_BYTES
+            + code.len() * INSTRUCTION_BYTES
+            + memory.len() * MEMORY_CELL_BYTES,
+    );
+    bytes.extend(MAGIC);
+    bytes.push(prog.entry_point);
+    let MemoryConfig { readable, writable } = &prog.memory_config;
+    for range in [readable, writable] {
+        bytes.extend(range.start().to_le_bytes());
+        bytes.extend(range.end().to_le_bytes());
+    }
+    // At most one instruction per `u8` program counter, so this fits
+    bytes.extend((code.len() as u16).to_le_bytes());
+    bytes.extend((memory.len() as u32).to_le_bytes());
+
+    for (&pc, instruction) in code {
+        let [operand_a, operand_b] = instruction.get_operands();
+        bytes.push(pc);
+        bytes.push(instruction.get_opcode());
+        // Operand A is always a register index
+        bytes.push(operand_a as u8);
+        bytes.extend(operand_b.to_le_bytes());
+    }
+    for (&address, &value) in memory {
+        bytes.extend(address.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes
+}
+
+/// Decodes a [Program] encoded by [to_bytes]. Errors on anything
+/// [to_bytes] would not produce, so that decoding and re-encoding gives
+/// back the very same bytes.
+pub fn from_bytes(bytes: &[u8]) -> Result<Program> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take::<4>()? != MAGIC {
+        return Err(anyhow!("missing magic bytes"));
+    }
+    let entry_point = reader.u8()?;
+    let memory_config = MemoryConfig {
+        readable: reader.range()?,
+        writable: reader.range()?,
+    };
+    let instructions = u16::from_le_bytes(reader.take()?);
+    let memory_cells = u32::from_le_bytes(reader.take()?);
+
+    let mut code = HashMap::new();
+    let mut last_pc = None;
+    for idx in 0..instructions {
+        let pc = reader.u8()?;
+        let opcode = reader.u8()?;
+        let operand_a = Word::from(reader.u8()?);
+        let operand_b = Word::from_le_bytes(reader.take()?);
+        if last_pc.is_some_and(|last_pc| pc <= last_pc) {
+            return Err(anyhow!("instruction {} is out of order", idx));
+        }
+        last_pc = Some(pc);
+        let instruction = Instruction::from_opcode_and_operands(
+            opcode,
+            [operand_a, operand_b],
+        )
+        .with_context(|| format!("instruction {}", idx))?;
+        code.insert(pc, instruction);
+    }
+
+    let mut memory_init = HashMap::new();
+    let mut last_address = None;
+    for idx in 0..memory_cells {
+        let address = Word::from_le_bytes(reader.take()?);
+        let value = Word::from_le_bytes(reader.take()?);
+        if last_address.is_some_and(|last_address| address <= last_address) {
+            return Err(anyhow!("memory cell {} is out of order", idx));
+        }
+        last_address = Some(address);
+        memory_init.insert(address, value);
+    }
+
+    if reader.offset != bytes.len() {
+        return Err(anyhow!(
+            "{} trailing bytes",
+            bytes.len() - reader.offset
+        ));
+    }
+    Ok(Program {
+        entry_point,
+        code,
+        memory_init,
+        memory_config,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let taken = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .ok_or_else(|| anyhow!("unexpected end at byte {}", self.offset))?;
+        self.offset += N;
+        Ok(taken
+            .try_into()
+            .expect("slice has length N"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        let [byte] = self.take()?;
+        Ok(byte)
+    }
+
+    fn range(&mut self) -> Result<RangeInclusive<Word>> {
+        let start = Word::from_le_bytes(self.take()?);
+        let end = Word::from_le_bytes(self.take()?);
+        Ok(start..=end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assembler::assemble,
+        fixtures,
+    };
+
+    #[test]
+    /// The add program survives a round trip, and encoding it again gives
+    /// the very same bytes
+    fn test_round_trip_add() {
+        let program = fixtures::add();
+        let bytes = to_bytes(&program);
+        assert_eq!(
+            bytes.len(),
+            HEADER_BYTES + 5 * INSTRUCTION_BYTES + 2 * MEMORY_CELL_BYTES
+        );
+
+        let decoded = from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, program);
+        assert_eq!(to_bytes(&decoded), bytes);
+    }
+
+    #[test]
+    /// Instructions with every kind of operand, sparse program counters,
+    /// large addresses and values and a memory config survive a round trip
+    fn test_round_trip_operands() {
+        let program = assemble(
+            "
+            .entry 0x01
+            .data 0xffffffff = 0xdeadbeef
+            .data 0x00 = 0x01
+                lis r3, -128
+                li r2, 0xffffffff
+                lb r1, [0x12345678]
+                jnz r1, 0x00
+                not r0
+                slt r0, r3
+                halt
+            ",
+        )
+        .unwrap();
+        let program = Program {
+            code: program
+                .code
+                .into_iter()
+                .map(|(pc, inst)| (pc * 3, inst))
+                .collect(),
+            memory_config: MemoryConfig {
+                readable: 0..=0xffff,
+                writable: 0x100..=0x1ff,
+            },
+            ..program
+        };
+        assert_eq!(from_bytes(&to_bytes(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        let bytes = to_bytes(&fixtures::add());
+
+        assert!(from_bytes(&[]).is_err());
+        assert!(from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(from_bytes(&wrong_magic).is_err());
+
+        // Opcode of the first instruction
+        let mut unknown_opcode = bytes.clone();
+        unknown_opcode[HEADER_BYTES + 1] = 0xff;
+        assert!(from_bytes(&unknown_opcode).is_err());
+
+        // Program counter of the second instruction, same as the first
+        let mut out_of_order = bytes;
+        out_of_order[HEADER_BYTES + INSTRUCTION_BYTES] = 0;
+        assert!(from_bytes(&out_of_order).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Interactive, one instruction at a time, execution of a [Program]. Runs
+//! the very same [SimulationRow::execute_one_cycle] as
+//! [PreflightSimulation::simulate], so what is seen here is what ends up in
+//! the trace.
+
+use crate::{
+    error::PixieError,
+    preflight_simulator::{
+        PreflightSimulation,
+        SimulationRow,
+    },
+    vm_specs::{
+        Program,
+        Word,
+        REGISTER_COUNT,
+    },
+};
+
+pub struct Debugger {
+    program: Program,
+
+    /// Row about to execute: its registers and memory are the machine
+    /// state before its instruction runs
+    current: SimulationRow,
+
+    /// Number of rows produced so far, `current` included
+    cycles: usize,
+
+    /// Most rows the execution may produce, see
+    /// [PreflightSimulation::simulate_with_budget]
+    max_cycles: usize,
+}
+
+impl Debugger {
+    /// Debugger stopped at the entry point of `program`, allowed as many
+    /// cycles as [PreflightSimulation::simulate]. Errors if `program` does
+    /// not pass [Program::validate].
+    pub fn new(program: Program) -> Result<Self, PixieError> {
+        Self::with_budget(program, PreflightSimulation::MAX_CPU_CYCLES_ALLOWED)
+    }
+
+    /// Like [Self::new], but allowing `max_cycles` CPU cycles
+    pub fn with_budget(
+        program: Program,
+        max_cycles: usize,
+    ) -> Result<Self, PixieError> {
+        program.validate()?;
+        let current = SimulationRow::generate_first_row(&program)?;
+        Ok(Self {
+            program,
+            current,
+            cycles: 1,
+            max_cycles,
+        })
+    }
+
+    /// Executes the instruction of the current row, moving on to the next.
+    /// Once halted, stepping is a no-op. Errors, without moving, if the
+    /// step would exceed the cycle budget.
+    pub fn step(&mut self) -> Result<&SimulationRow, PixieError> {
+        if self.is_halted() {
+            return Ok(&self.current);
+        }
+        if self.cycles >= self.max_cycles {
+            return Err(PixieError::CycleBudgetExceeded {
+                budget: self.max_cycles,
+            });
+        }
+        self.current = self
+            .current
+            .execute_one_cycle(&self.program)?;
+        self.cycles += 1;
+        Ok(&self.current)
+    }
+
+    /// Steps at least once, then until the current row is at `pc`, or the
+    /// execution halted
+    pub fn run_until(
+        &mut self,
+        pc: u8,
+    ) -> Result<&SimulationRow, PixieError> {
+        self.step()?;
+        while self
+            .current
+            .program_counter
+            != pc
+            && !self.is_halted()
+        {
+            self.step()?;
+        }
+        Ok(&self.current)
+    }
+
+    /// Row about to execute
+    pub fn current(&self) -> &SimulationRow {
+        &self.current
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.current
+            .is_halted
+    }
+
+    /// Registers before the current row executes
+    pub fn registers(&self) -> [Word; REGISTER_COUNT] {
+        self.current
+            .get_registers()
+    }
+
+    /// Memory before the current row executes
+    pub fn memory(&self) -> &im::HashMap<Word, Word> {
+        &self
+            .current
+            .memory_snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    /// Single steps the add program, checking the registers after each
+    /// instruction
+    fn test_step_add() {
+        let mut debugger = Debugger::new(fixtures::add()).unwrap();
+        assert_eq!(debugger.registers(), [0, 0, 0, 0]);
+
+        let expected = [
+            [0x20, 0, 0, 0],
+            [0x20, 0x45, 0, 0],
+            [0x65, 0x45, 0, 0],
+            [0x65, 0x45, 0, 0],
+        ];
+        for (idx, registers) in expected
+            .into_iter()
+            .enumerate()
+        {
+            let row = debugger
+                .step()
+                .unwrap();
+            assert_eq!(row.program_counter, idx as u8 + 1);
+            assert_eq!(debugger.registers(), registers);
+        }
+        assert!(debugger.is_halted());
+        assert_eq!(
+            debugger
+                .memory()
+                .get(&0x42),
+            Some(&0x65)
+        );
+
+        // Stepping past `Halt` stays put
+        let clock = debugger
+            .current()
+            .clock;
+        assert_eq!(
+            debugger
+                .step()
+                .unwrap()
+                .clock,
+            clock
+        );
+    }
+
+    #[test]
+    /// `run_until` stops at the requested pc, or at `Halt` if never there
+    fn test_run_until() {
+        let mut debugger = Debugger::new(fixtures::simple_loop()).unwrap();
+        let row = debugger
+            .run_until(0x03)
+            .unwrap();
+        assert_eq!(row.program_counter, 0x03);
+
+        // Steps at least once, so this is the next iteration's `Sb`
+        let clock = row.clock;
+        let row = debugger
+            .run_until(0x03)
+            .unwrap();
+        assert_eq!(row.program_counter, 0x03);
+        assert!(row.clock > clock);
+
+        let row = debugger
+            .run_until(0xff)
+            .unwrap();
+        assert!(row.is_halted);
+    }
+
+    #[test]
+    /// Running out of cycles errors rather than looping forever
+    fn test_cycle_budget() {
+        let mut debugger =
+            Debugger::with_budget(fixtures::countdown(10), 5).unwrap();
+        assert_eq!(
+            debugger
+                .run_until(0xff)
+                .unwrap_err(),
+            PixieError::CycleBudgetExceeded { budget: 5 }
+        );
+        assert_eq!(
+            debugger
+                .current()
+                .clock,
+            5
+        );
+    }
+}
@@ -1,97 +1,616 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::BTreeSet;
 
     use plonky2::{
-        field::goldilocks_field::GoldilocksField,
+        field::{
+            goldilocks_field::GoldilocksField,
+            polynomial::PolynomialValues,
+            types::{
+                Field,
+                PrimeField64,
+            },
+        },
+        iop::challenger::Challenger,
         plonk::config::{
             GenericConfig,
             PoseidonGoldilocksConfig,
         },
-        util::timing::TimingTree,
     };
     use starky::{
         config::StarkConfig,
-        proof::StarkProofWithPublicInputs,
-        prover::prove,
-        verifier::verify_stark_proof,
+        lookup::get_grand_product_challenge_set,
     };
 
     use crate::{
+        assembler::assemble,
+        fixtures,
         preflight_simulator::PreflightSimulation,
-        stark_program_instructions::ProgramInstructionsStark,
+        stark_cpu::{
+            CPUStark,
+            MEMORY_OP_COLS,
+            NUM_STATE_COLS,
+        },
+        stark_memory::{
+            MemoryStark,
+            CPU_OP_COLS,
+        },
+        stark_pixie_zkvm::{
+            assemble_and_prove,
+            config_for_trace_len,
+            generate_chunked_proof,
+            generate_proof,
+            generate_proof_with_outputs,
+            generate_window_proof,
+            known_program_columns,
+            prove_all,
+            verify_all,
+            verify_chunked_proof,
+            verify_proof,
+            verify_window_proof,
+            PixiePublicValues,
+            PixieZKVM,
+            StarkSet,
+            Table,
+            SHORT_TRACE_LEN,
+        },
         vm_specs::{
             Instruction,
+            InstructionLocation,
             MemoryLocation,
-            Program,
+            ProgramBuilder,
             Register,
+            OPCODE_COUNT,
+            REGISTER_COUNT,
         },
+        workloads,
     };
 
     #[test]
+    /// Proves and verifies the add program through `prove_all`, all the
+    /// tables on one shared transcript
     fn test_add_program() {
-        let instructions = vec![
-            Instruction::Lb(Register::R0, MemoryLocation(0x40)),
-            Instruction::Lb(Register::R1, MemoryLocation(0x41)),
-            Instruction::Add(Register::R0, Register::R1),
-            Instruction::Sb(Register::R0, MemoryLocation(0x42)),
-            Instruction::Halt,
-        ];
-
-        let code = instructions
-            .into_iter()
-            .enumerate()
-            .map(|(idx, inst)| (idx as u8, inst))
-            .collect::<HashMap<u8, Instruction>>();
-
-        let memory_init: HashMap<u8, u8> =
-            HashMap::from_iter(vec![(0x40, 0x20), (0x41, 0x45)]);
-
-        let program = Program {
-            entry_point: 0,
-            code,
-            memory_init,
-        };
+        // D = 2 for quadratic extension
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let program = fixtures::add();
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        let simulation = simulation.unwrap();
 
-        // Stark specific setup
+        // The program table is linked to the CPU table, so it can only be
+        // proven along with the rest of the tables
+        let tables =
+            StarkSet::<F>::from_simulation::<D>(&program, &simulation);
+        assert_eq!(
+            tables.traces[Table::ProgramInstructions as usize][0].len(),
+            program
+                .code
+                .len()
+                .next_power_of_two()
+        );
+        let public_inputs = tables
+            .public_inputs
+            .clone();
+
+        let zkvm = PixieZKVM::<F, D>::new();
+        let proof = prove_all::<F, C, D>(&zkvm, tables);
+        assert!(proof.is_ok());
+        let proof = proof.unwrap();
+        assert_eq!(proof.public_inputs, public_inputs);
+
+        let verification = verify_all(
+            &zkvm,
+            &proof,
+            &known_program_columns::<F, D>(
+                &program,
+                Some(&program.memory_init),
+                None,
+            ),
+        );
+        assert!(verification.is_ok());
+        assert!(verify_proof(&proof, &program).is_ok());
+    }
+
+    /// Returns `(clk, addr)` for every row of `trace` whose `filter_cols`
+    /// sum to one, sorted so that both sides of a lookup can be compared
+    fn filtered_rows<F: PrimeField64>(
+        trace: &[PolynomialValues<F>],
+        filter_cols: &[usize],
+        clk_col: usize,
+        addr_col: usize,
+    ) -> Vec<(u64, u64)> {
+        let mut rows = (0..trace[0].len())
+            .filter(|&row| {
+                filter_cols
+                    .iter()
+                    .map(|&col| trace[col].values[row])
+                    .sum::<F>()
+                    == F::ONE
+            })
+            .map(|row| {
+                (
+                    trace[clk_col].values[row].to_canonical_u64(),
+                    trace[addr_col].values[row].to_canonical_u64(),
+                )
+            })
+            .collect::<Vec<(u64, u64)>>();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    /// An arithmetic-heavy program with only a few memory operations. Only
+    /// the `Lb` / `Sb` rows of the CPU table may take part in the lookup
+    /// into the memory table, everything else (including `Halt`) must be
+    /// filtered out on both sides for the lookup to link.
+    fn test_memory_ctl_filter_arithmetic_heavy_program() {
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0x03)
+            .memory(0x41, 0x02)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x41)))
+            .instruction(Instruction::Add(Register::R0, Register::R1))
+            .instruction(Instruction::Mul(Register::R0, Register::R1))
+            .instruction(Instruction::Sub(Register::R0, Register::R1))
+            .instruction(Instruction::Add(Register::R1, Register::R0))
+            .instruction(Instruction::Div(Register::R0, Register::R1))
+            .instruction(Instruction::Shl(Register::R1, 3))
+            .instruction(Instruction::Shr(Register::R1, 2))
+            .instruction(Instruction::Add(Register::R0, Register::R1))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        const D: usize = 2;
+        type F = GoldilocksField;
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        let simulation = simulation.unwrap();
+
+        let cpu_trace = CPUStark::<F, D>::generate_trace(&simulation);
+        let mem_trace = MemoryStark::<F, D>::generate_trace(&simulation);
+
+        // CPU: `clk` is column 0, `loc` follows `clk`, `pc` and the
+        // registers
+        let looking =
+            filtered_rows(&cpu_trace, &MEMORY_OP_COLS, 0, 2 + REGISTER_COUNT);
+        // Memory: `addr` is column 0, `clk` is column 1
+        let looked = filtered_rows(&mem_trace, &CPU_OP_COLS, 1, 0);
+
+        assert_eq!(looking.len(), 3);
+        assert_eq!(looking, looked);
+    }
+
+    #[test]
+    /// A loop which runs for 9 cycles, proven in two chunks of CPU rows
+    fn test_chunked_loop_program() {
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0x03)
+            .memory(0x41, 0x01)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x41)))
+            .instruction(Instruction::Sub(Register::R0, Register::R1))
+            .instruction(Instruction::Jnz(
+                Register::R0,
+                InstructionLocation(0x02),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
 
-        // D = 2 for quadratic extension
         const D: usize = 2;
         type C = PoseidonGoldilocksConfig;
         type F = <C as GenericConfig<D>>::F;
-        type PR = StarkProofWithPublicInputs<GoldilocksField, C, 2>;
 
-        let mut config = StarkConfig::standard_fast_config();
-        // This needs to be done for tables shorter than `1<<5`. We take
-        // a performance hit though!
-        // Number of cap = 2^{cap_height}
-        config
-            .fri_config
-            .cap_height = 1;
-
-        // Generate the static part of the proof
-        let program_proof = {
-            type S = ProgramInstructionsStark<F, D>;
-
-            let stark = S::new();
-            let trace_poly_values =
-                ProgramInstructionsStark::<F, D>::generate_trace(&program);
-            let proof: Result<PR, anyhow::Error> = prove(
-                stark.clone(),
-                &config,
-                trace_poly_values,
-                &[],
-                &mut TimingTree::default(),
-            );
-            assert!(proof.is_ok());
-            let proof = proof.unwrap();
-            let verification =
-                verify_stark_proof(stark, proof.clone(), &config);
-            assert!(verification.is_ok());
+        let proof = generate_chunked_proof::<F, C, D>(&program, 5);
+        assert!(proof.is_ok());
+        let mut proof = proof.unwrap();
+        assert_eq!(
             proof
+                .chunk_proofs
+                .len(),
+            2
+        );
+
+        // End of the first chunk is the start of the second one
+        let boundary_public_inputs = proof
+            .chunk_proofs
+            .iter()
+            .map(|chunk_proof| {
+                chunk_proof.public_inputs[Table::Cpu as usize]
+                    .iter()
+                    .map(|elem| elem.to_canonical_u64())
+                    .collect::<Vec<u64>>()
+            })
+            .collect::<Vec<Vec<u64>>>();
+        assert_eq!(
+            boundary_public_inputs[0][NUM_STATE_COLS..2 * NUM_STATE_COLS],
+            boundary_public_inputs[1][..NUM_STATE_COLS]
+        );
+        // Clock of the shared row
+        assert_eq!(boundary_public_inputs[0][NUM_STATE_COLS], 6);
+
+        assert!(verify_chunked_proof(&proof, &program).is_ok());
+        assert_eq!(proof.boundary_memories[0], program.memory_init);
+
+        // Chunks don't chain up if a boundary is swapped out
+        proof
+            .boundary_commitments
+            .swap(1, 2);
+        assert!(verify_chunked_proof(&proof, &program).is_err());
+        proof
+            .boundary_commitments
+            .swap(1, 2);
+
+        // Memory handed over to the second chunk which the first one does
+        // not end with
+        proof.boundary_memories[1]
+            .insert(0x50, 0x07);
+        assert!(verify_chunked_proof(&proof, &program).is_err());
+        proof.boundary_memories[1]
+            .remove(&0x50);
+        assert!(verify_chunked_proof(&proof, &program).is_ok());
+
+        // The second chunk of an execution with one more memory cell, never
+        // accessed, has the same CPU boundaries and is consistent with its
+        // own boundary memories, but those do not chain to the first chunk
+        let mut other_program = program.clone();
+        other_program
+            .memory_init
+            .insert(0x50, 0x07);
+        let other_proof =
+            generate_chunked_proof::<F, C, D>(&other_program, 5).unwrap();
+        let mut other_chunks = other_proof
+            .chunk_proofs
+            .into_iter();
+        proof.chunk_proofs[1] = other_chunks
+            .nth(1)
+            .unwrap();
+        proof.boundary_memories[1..]
+            .clone_from_slice(&other_proof.boundary_memories[1..]);
+        assert!(verify_chunked_proof(&proof, &program).is_err());
+    }
+
+    #[test]
+    /// Two adjacent windows of a loop, proven and verified independently
+    fn test_window_proofs_loop_program() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let program = fixtures::simple_loop();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let last_clock = simulation
+            .trace_rows
+            .last()
+            .unwrap()
+            .clock;
+
+        let first =
+            generate_window_proof::<F, C, D>(&program, &simulation, 1, 6);
+        assert!(first.is_ok());
+        let mut first = first.unwrap();
+        let second = generate_window_proof::<F, C, D>(
+            &program,
+            &simulation,
+            6,
+            last_clock,
+        );
+        assert!(second.is_ok());
+        let mut second = second.unwrap();
+
+        // End of the first window is the start of the second one
+        assert_eq!(first.boundaries().1, second.boundaries().0);
+        assert_eq!(first.boundaries().1[0], F::from_canonical_u32(6));
+        assert_eq!(first.memory_start, program.memory_init);
+        assert_eq!(first.memory_end, second.memory_start);
+
+        assert!(verify_window_proof(&first, &program).is_ok());
+        assert!(verify_window_proof(&second, &program).is_ok());
+
+        // Neither window verifies with memory other than it was proven
+        // with, nor can the first one start from other than the initial
+        // memory of the program
+        second
+            .memory_start
+            .insert(0x50, 0x07);
+        assert!(verify_window_proof(&second, &program).is_err());
+        first
+            .memory_end
+            .insert(0x50, 0x07);
+        assert!(verify_window_proof(&first, &program).is_err());
+        first
+            .memory_end
+            .remove(&0x50);
+        first
+            .memory_start
+            .insert(0x50, 0x07);
+        assert!(verify_window_proof(&first, &program).is_err());
+
+        // Windows need cycles, which need to be part of the execution
+        assert!(
+            generate_window_proof::<F, C, D>(&program, &simulation, 6, 6)
+                .is_err()
+        );
+        assert!(generate_window_proof::<F, C, D>(
+            &program,
+            &simulation,
+            6,
+            last_clock + 1
+        )
+        .is_err());
+    }
+
+    #[test]
+    /// Proves the add program through the top level entry point
+    fn test_generate_proof_add_program() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let proof = generate_proof::<F, C, D>(&fixtures::add());
+        assert!(proof.is_ok());
+    }
+
+    #[test]
+    /// Round trip through `generate_proof` and `verify_proof`, along with
+    /// the ways a proof must fail to verify
+    fn test_verify_proof_add_program() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let program = fixtures::add();
+        let proof = generate_proof::<F, C, D>(&program);
+        assert!(proof.is_ok());
+        let proof = proof.unwrap();
+        assert!(verify_proof(&proof, &program).is_ok());
+
+        // A single element of the CPU trace commitment is changed
+        let mut tampered = proof.clone();
+        tampered
+            .multi_proof
+            .stark_proofs[Table::Cpu as usize]
+            .proof
+            .trace_cap
+            .0[0]
+            .elements[0] += F::ONE;
+        assert!(verify_proof(&tampered, &program).is_err());
+
+        // Challenges drawn from a transcript observing the trace caps in
+        // the reverse order
+        let mut challenger =
+            Challenger::<F, <C as GenericConfig<D>>::Hasher>::new();
+        for stark_proof in proof
+            .multi_proof
+            .stark_proofs
+            .iter()
+            .rev()
+        {
+            challenger.observe_cap(
+                &stark_proof
+                    .proof
+                    .trace_cap,
+            );
+        }
+        for table_public_inputs in &proof.public_inputs {
+            challenger.observe_elements(table_public_inputs);
+        }
+        let reordered_challenges = get_grand_product_challenge_set(
+            &mut challenger,
+            StarkConfig::standard_fast_config().num_challenges,
+        );
+        assert_ne!(reordered_challenges, proof.ctl_challenges);
+        let mut reordered = proof.clone();
+        reordered.ctl_challenges = reordered_challenges;
+        assert!(verify_proof(&reordered, &program).is_err());
+    }
+
+    #[test]
+    /// A proof of one program is no proof of another, be it for its code
+    /// or for its initial memory
+    fn test_verify_proof_of_other_program() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let program = fixtures::add();
+        let proof = generate_proof::<F, C, D>(&program).unwrap();
+        assert!(verify_proof(&proof, &program).is_ok());
+
+        let mut other_code = program.clone();
+        other_code
+            .code
+            .insert(2, Instruction::Sub(Register::R0, Register::R1));
+        assert!(verify_proof(&proof, &other_code).is_err());
+
+        let mut other_memory = program.clone();
+        other_memory
+            .memory_init
+            .insert(0x40, 0x21);
+        assert!(verify_proof(&proof, &other_memory).is_err());
+
+        let mut more_memory = program.clone();
+        more_memory
+            .memory_init
+            .insert(0x43, 0x00);
+        assert!(verify_proof(&proof, &more_memory).is_err());
+
+        // Nor against another program altogether
+        assert!(verify_proof(&proof, &fixtures::simple_loop()).is_err());
+    }
+
+    #[test]
+    /// Only traces shorter than `1<<5` get a lowered cap height, which
+    /// `verify_proof` reads back off the proof
+    fn test_config_for_trace_len() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let default_cap_height = StarkConfig::standard_fast_config()
+            .fri_config
+            .cap_height;
+        let cap_height = |trace_len: usize| {
+            config_for_trace_len(trace_len)
+                .fri_config
+                .cap_height
         };
+        assert_eq!(cap_height(5), 1);
+        assert_eq!(cap_height(1 << 8), default_cap_height);
+        assert_eq!(cap_height(SHORT_TRACE_LEN - 1), 1);
+        assert_eq!(cap_height(SHORT_TRACE_LEN), default_cap_height);
 
-        // Simuate the program PreFlight
-        let simulation = PreflightSimulation::simulate(&program);
+        // Every table of the largest churn pads to `1<<8` rows
+        for (program, expected) in [
+            (fixtures::add(), 1),
+            (
+                workloads::memory_churn(workloads::MAX_CHURN_CELLS),
+                default_cap_height,
+            ),
+        ] {
+            let proof = generate_proof::<F, C, D>(&program).unwrap();
+            for stark_proof in &proof
+                .multi_proof
+                .stark_proofs
+            {
+                assert_eq!(
+                    stark_proof
+                        .proof
+                        .trace_cap
+                        .0
+                        .len(),
+                    1 << expected
+                );
+            }
+            assert!(verify_proof(&proof, &program).is_ok());
+        }
+    }
+
+    #[test]
+    /// The immediate of `Li` is part of the instruction looked up in the
+    /// program table
+    fn test_load_immediate_program() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0x2a))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let proof = generate_proof::<F, C, D>(&program);
+        assert!(proof.is_ok());
+        assert!(verify_proof(&proof.unwrap(), &program).is_ok());
+    }
+
+    #[test]
+    /// Pins down which opcodes have their semantics constrained. The set
+    /// only ever grows: whenever constraints for an opcode land, add it
+    /// here along with a test that tampering with its result fails.
+    fn test_constrained_opcodes() {
+        const D: usize = 2;
+        type F = GoldilocksField;
+
+        let constrained = PixieZKVM::<F, D>::constrained_opcodes();
+        assert_eq!(
+            constrained,
+            BTreeSet::from([
+                Instruction::Halt.get_opcode(),
+                Instruction::Mov(Register::R0, Register::R0).get_opcode(),
+            ])
+        );
+        assert!(constrained
+            .iter()
+            .all(|&opcode| usize::from(opcode) < OPCODE_COUNT));
+
+        let add = fixtures::add();
+        let opcodes = add
+            .code
+            .values()
+            .map(Instruction::get_opcode)
+            .filter(|opcode| !constrained.contains(opcode))
+            .collect::<BTreeSet<u8>>();
+        assert_eq!(PixieZKVM::<F, D>::unconstrained_opcodes(&add), opcodes);
+    }
+
+    #[test]
+    /// Assembly goes all the way to a verifying proof in one call, and
+    /// failures name the stage they come from
+    fn test_assemble_and_prove() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let src = "
+            .data 0x40 = 0x06
+                li r1, 2
+                lb r0, [0x40]
+                mul r0, r1
+                sb r0, [0x42]
+                halt
+        ";
+        let proof = assemble_and_prove::<F, C, D>(src).unwrap();
+        assert!(verify_proof(&proof, &assemble(src).unwrap()).is_ok());
+
+        let err = assemble_and_prove::<F, C, D>("li r0, 1\nnop r1, r0")
+            .unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "assembling the program: line 2: unknown mnemonic `nop`"
+        );
+
+        let err = assemble_and_prove::<F, C, D>("li r0, 1\njnz r0, 0\nhalt")
+            .unwrap_err();
+        assert!(format!("{:#}", err).starts_with("simulating the program: "));
+    }
+
+    #[test]
+    /// The add program provably ends with `0x65` in `R0` and at `0x42`, and
+    /// a proof claiming otherwise does not verify
+    fn test_public_values_add_program() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let program = fixtures::add();
+        let proof =
+            generate_proof_with_outputs::<F, C, D>(&program, &[0x42]).unwrap();
+        assert_eq!(
+            verify_proof(&proof, &program).unwrap(),
+            PixiePublicValues {
+                final_registers: [0x65, 0x45, 0, 0],
+                final_memory: vec![(0x42, 0x65)],
+            }
+        );
+
+        // Public inputs of the memory table are `(Is_Claimed, Address,
+        // Value)` of each output
+        let mut wrong_output = proof.clone();
+        wrong_output.public_inputs[Table::Memory as usize][2] =
+            F::from_canonical_u32(0x66);
+        assert!(verify_proof(&wrong_output, &program).is_err());
+
+        // Without outputs, only the registers are public
+        let proof = generate_proof::<F, C, D>(&program).unwrap();
+        assert_eq!(
+            verify_proof(&proof, &program)
+                .unwrap()
+                .final_memory,
+            vec![]
+        );
+
+        // `0x50` is never written to, so it holds no provable value
+        assert!(
+            generate_proof_with_outputs::<F, C, D>(&program, &[0x50]).is_err()
+        );
     }
 }
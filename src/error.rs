@@ -0,0 +1,55 @@
+//! Errors of the VM which callers may want to tell apart. Everything
+//! proving related stays an `anyhow::Error`, into which a [PixieError]
+//! converts with `?`.
+
+use thiserror::Error;
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum PixieError {
+    /// The entry point of the program holds no instruction
+    #[error("entry point holds no instruction")]
+    EntryPointMissing,
+
+    /// A `Jz`, `Jnz` or `Call` targets a location holding no instruction
+    #[error(
+        "jump at pc={pc} targets pc={target}, which holds no instruction"
+    )]
+    JumpTargetMissing { pc: u8, target: u8 },
+
+    /// No path of execution from the entry point reaches a `Halt`
+    #[error("no `Halt` is reachable from the entry point")]
+    HaltUnreachable,
+
+    /// Execution continued to a location holding no instruction
+    #[error("instruction not found at pc={pc}")]
+    InstructionNotFound { pc: u8 },
+
+    /// The program did not halt within the allowed number of CPU cycles
+    #[error(
+        "simulation halted since the budget of {budget} CPU cycles was \
+         reached"
+    )]
+    CycleBudgetExceeded { budget: usize },
+
+    /// The machine came back to a state it was in before, so it never
+    /// halts
+    #[error("execution loops forever, state repeats at pc={pc}")]
+    NonTerminating { pc: u8 },
+
+    /// A `Div` had a zero divisor
+    #[error("division by zero at pc={pc}")]
+    DivByZero { pc: u8 },
+
+    /// A `Ret` executed with no `Call` left to return from
+    #[error("return at pc={pc} with an empty return stack")]
+    StackUnderflow { pc: u8 },
+
+    /// A load outside of the readable or a store outside of the writable
+    /// addresses of the program, see `MemoryConfig`
+    #[error("memory access at pc={pc} to {addr:#x} is not permitted")]
+    MemoryAccessViolation { addr: u32, pc: u8 },
+
+    /// A store needed a new memory cell, but the memory was full
+    #[error("memory capacity of {capacity} cells exceeded")]
+    MemoryCapacityExceeded { capacity: usize },
+}
@@ -0,0 +1,171 @@
+//! Canonical programs shared by tests across the crate, along with the
+//! machinery to lock their execution down as "golden" traces checked in
+//! under `golden/`.
+
+use crate::vm_specs::{
+    Instruction,
+    InstructionLocation,
+    MemoryLocation,
+    Program,
+    ProgramBuilder,
+    Register,
+    Word,
+};
+
+/// Environment variable which, when set, makes golden tests rewrite the
+/// golden files instead of comparing against them
+pub const GOLDEN_UPDATE_ENV_VAR: &str = "PIXIE_UPDATE_GOLDEN";
+
+fn program_from(
+    instructions: Vec<Instruction>,
+    memory_init: Vec<(Word, Word)>,
+) -> Program {
+    let builder = memory_init
+        .into_iter()
+        .fold(ProgramBuilder::new(), |builder, (address, value)| {
+            builder.memory(address, value)
+        });
+    instructions
+        .into_iter()
+        .fold(builder, ProgramBuilder::instruction)
+        .build()
+        .expect("fixture programs fit in the code space")
+}
+
+/// Adds two numbers from memory and stores the result at `0x42`
+pub fn add() -> Program {
+    program_from(
+        vec![
+            Instruction::Lb(Register::R0, MemoryLocation(0x40)),
+            Instruction::Lb(Register::R1, MemoryLocation(0x41)),
+            Instruction::Add(Register::R0, Register::R1),
+            Instruction::Sb(Register::R0, MemoryLocation(0x42)),
+            Instruction::Halt,
+        ],
+        vec![(0x40, 0x20), (0x41, 0x45)],
+    )
+}
+
+/// Subtracts `0x41` from `0x40` until zero, storing the intermediate
+/// values to `0x42` on every iteration
+pub fn simple_loop() -> Program {
+    program_from(
+        vec![
+            Instruction::Lb(Register::R0, MemoryLocation(0x40)),
+            Instruction::Lb(Register::R1, MemoryLocation(0x41)),
+            Instruction::Sub(Register::R0, Register::R1),
+            Instruction::Sb(Register::R0, MemoryLocation(0x42)),
+            Instruction::Jnz(Register::R0, InstructionLocation(0x02)),
+            Instruction::Halt,
+        ],
+        vec![(0x40, 0x06), (0x41, 0x02)],
+    )
+}
+
+/// Counts `R0` down from `n` to 0 with a conditional jump. Executes in
+/// exactly `2 * n + 4` rows, which makes it handy for targeting specific
+/// trace lengths.
+pub fn countdown(n: Word) -> Program {
+    program_from(
+        vec![
+            Instruction::Li(Register::R0, n),
+            Instruction::Li(Register::R1, 1),
+            Instruction::Jz(Register::R0, InstructionLocation(0x05)),
+            Instruction::Sub(Register::R0, Register::R1),
+            Instruction::Jnz(Register::R0, InstructionLocation(0x03)),
+            Instruction::Halt,
+        ],
+        vec![],
+    )
+}
+
+/// Compares `actual` against the golden file `golden/<name>.golden`. With
+/// [GOLDEN_UPDATE_ENV_VAR] set, (re)writes the file instead.
+#[cfg(test)]
+pub fn assert_golden(
+    name: &str,
+    actual: &str,
+) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}.golden", name));
+
+    if std::env::var_os(GOLDEN_UPDATE_ENV_VAR).is_some() {
+        std::fs::create_dir_all(
+            path.parent()
+                .expect("golden file has a parent directory"),
+        )
+        .expect("golden directory should be creatable");
+        std::fs::write(&path, actual).expect("golden file should be writable");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "missing golden file {}, run with {}=1 to create it",
+            path.display(),
+            GOLDEN_UPDATE_ENV_VAR
+        )
+    });
+    assert_eq!(
+        expected,
+        actual,
+        "trace differs from {}, run with {}=1 if this is intended",
+        path.display(),
+        GOLDEN_UPDATE_ENV_VAR
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preflight_simulator::PreflightSimulation;
+
+    fn assert_golden_simulation(
+        name: &str,
+        program: &Program,
+    ) {
+        let simulation = PreflightSimulation::simulate(program);
+        assert!(simulation.is_ok());
+        let simulation = simulation.unwrap();
+        assert_golden(name, &simulation.to_golden_string());
+    }
+
+    #[test]
+    fn test_golden_add() {
+        assert_golden_simulation("add", &add());
+    }
+
+    #[test]
+    fn test_golden_simple_loop() {
+        assert_golden_simulation("simple_loop", &simple_loop());
+    }
+
+    #[test]
+    fn test_golden_countdown() {
+        assert_golden_simulation("countdown", &countdown(5));
+    }
+
+    #[test]
+    fn test_countdown_cycle_count() {
+        for n in [0, 1, 5, 100] {
+            let simulation = PreflightSimulation::simulate(&countdown(n));
+            assert!(simulation.is_ok());
+            let simulation = simulation.unwrap();
+            assert_eq!(
+                simulation
+                    .trace_rows
+                    .len(),
+                2 * n as usize + 4
+            );
+            assert_eq!(
+                simulation
+                    .trace_rows
+                    .last()
+                    .unwrap()
+                    .registers[0],
+                0
+            );
+        }
+    }
+}
@@ -12,12 +12,26 @@
 // We allow for dead_code because a usage of such in test harnesses
 // doesn't register as a usage for clippy
 #[allow(dead_code)]
+mod assembler;
+#[allow(dead_code)]
+mod bytecode;
+#[allow(dead_code)]
+mod debugger;
+#[allow(dead_code)]
+mod error;
+#[allow(dead_code)]
+mod fixtures;
+#[allow(dead_code)]
 mod preflight_simulator;
 #[allow(dead_code)]
 mod utilities;
+mod utility_macros;
 #[allow(dead_code)]
 mod vm_specs;
 
+// Benchmark workloads, public for `benches/`
+pub mod workloads;
+
 // STARK tables -------------
 #[allow(dead_code)]
 mod stark_cpu;
@@ -26,11 +40,15 @@ mod stark_program_instructions;
 
 #[allow(dead_code)]
 mod stark_memory;
-//mod stark_rangecheck_u8;
+#[allow(dead_code)]
+mod stark_rangecheck_u8;
 //mod stark_execution_program_subset;
 
 #[allow(dead_code)]
 mod stark_pixie_zkvm;
+#[allow(dead_code)]
+#[cfg(feature = "serde")]
+mod proof_serialization;
 
 // END TO END TEST ----------
 #[allow(dead_code)]
@@ -1,17 +1,115 @@
-use std::collections::HashMap;
-
-use anyhow::{
-    anyhow,
-    Context,
-    Result,
+use std::collections::{
+    HashMap,
+    HashSet,
 };
 
-use crate::vm_specs::{
-    Instruction,
-    Program,
-    REGISTER_COUNT,
+use crate::{
+    error::PixieError,
+    vm_specs::{
+        Instruction,
+        Program,
+        Word,
+        REGISTER_COUNT,
+    },
 };
 
+/// Storage backing the memory of the VM. Backends are bounded: a store
+/// which would grow a memory beyond its [Memory::capacity] errors.
+pub trait Memory {
+    /// Maximum number of cells the memory can hold
+    fn capacity(&self) -> usize;
+
+    /// Number of cells currently held
+    fn cells(&self) -> usize;
+
+    fn load(
+        &self,
+        address: Word,
+    ) -> Option<Word>;
+
+    /// Writes `value` at `address`, without checking the capacity
+    fn insert(
+        &mut self,
+        address: Word,
+        value: Word,
+    );
+
+    /// Writes `value` at `address` if the memory can hold it
+    fn store(
+        &mut self,
+        address: Word,
+        value: Word,
+    ) -> Result<(), PixieError> {
+        if self
+            .load(address)
+            .is_none()
+            && self.cells() >= self.capacity()
+        {
+            return Err(PixieError::MemoryCapacityExceeded {
+                capacity: self.capacity(),
+            });
+        }
+        self.insert(address, value);
+        Ok(())
+    }
+}
+
+/// Most cells a memory holds. The [Word] address space is far too large to
+/// ever fill, so it is bounded well below it.
+pub const MEMORY_CAPACITY: usize = 1 << 16;
+
+impl Memory for HashMap<Word, Word> {
+    fn capacity(&self) -> usize {
+        MEMORY_CAPACITY
+    }
+
+    fn cells(&self) -> usize {
+        self.len()
+    }
+
+    fn load(
+        &self,
+        address: Word,
+    ) -> Option<Word> {
+        self.get(&address)
+            .copied()
+    }
+
+    fn insert(
+        &mut self,
+        address: Word,
+        value: Word,
+    ) {
+        HashMap::insert(self, address, value);
+    }
+}
+
+impl Memory for im::HashMap<Word, Word> {
+    fn capacity(&self) -> usize {
+        MEMORY_CAPACITY
+    }
+
+    fn cells(&self) -> usize {
+        self.len()
+    }
+
+    fn load(
+        &self,
+        address: Word,
+    ) -> Option<Word> {
+        self.get(&address)
+            .copied()
+    }
+
+    fn insert(
+        &mut self,
+        address: Word,
+        value: Word,
+    ) {
+        im::HashMap::insert(self, address, value);
+    }
+}
+
 /// Each `SimulationRow` describes the state of simulation at each step
 /// of execution
 #[derive(Debug)]
@@ -28,52 +126,56 @@ pub struct SimulationRow {
     pub program_counter: u8,
 
     /// Whether at this row the execution halted. Should only be true
-    /// for the last row in any `PreflightSimulation`
+    /// for the last row in any `PreflightSimulation`, which the CPU table
+    /// enforces for the rows executing `Halt`
     pub is_halted: bool,
 
     /// Registers
-    pub registers: [u8; REGISTER_COUNT],
+    pub registers: [Word; REGISTER_COUNT],
 
-    /// This ideally should be something like `im::HashMap`, see:
+    /// Memory before this row executes. An `im::HashMap`, see:
     /// https://crates.io/crates/im for immutable collections.
-    /// This is because, more often than not, each subsequent `SimulationRow`
-    /// will have very slightly changed memory snapshots. Maybe only one
-    /// address's value would have changed for example. Makes sense to
-    /// only store the `delta` from the previous hashmap rather than the
-    /// full hashmap like we are doing here.
-    ///
-    /// However, that optimization is not used for simplicity's sake and
-    /// since our VM is small, this is not a large performance hit.
-    pub memory_snapshot: HashMap<u8, u8>,
+    /// More often than not, each subsequent `SimulationRow` has a very
+    /// slightly changed memory snapshot, maybe only one address's value.
+    /// Cloning shares structure with the previous snapshot, so a row only
+    /// pays for the addresses it changes rather than the full memory.
+    pub memory_snapshot: im::HashMap<Word, Word>,
+
+    /// Locations the `Call`s not yet returned from come back to, the
+    /// innermost last
+    pub return_stack: Vec<u8>,
 }
 
 impl SimulationRow {
-    pub fn generate_first_row(prog: &Program) -> Result<Self> {
+    pub fn generate_first_row(prog: &Program) -> Result<Self, PixieError> {
         let program_counter = prog.entry_point;
         let instruction = prog
             .code
             .get(&program_counter)
             .cloned()
-            .context("instruction not found")?;
+            .ok_or(PixieError::EntryPointMissing)?;
         Ok(Self {
             instruction,
             clock: 1, // `0` is reserved for memory init
             program_counter,
             is_halted: false,
             registers: [0; REGISTER_COUNT],
-            memory_snapshot: prog
-                .memory_init
-                .clone(),
+            memory_snapshot: im::HashMap::from(&prog.memory_init),
+            return_stack: Vec::new(),
         })
     }
 
     pub fn execute_one_cycle(
         &self,
         prog: &Program,
-    ) -> Result<Self> {
+    ) -> Result<Self, PixieError> {
         // This is mutable precisely because jump instructions can change it
-        // in weird ways. This is good default for many other operations though
-        let mut program_counter = self.program_counter + 1;
+        // in weird ways. This is good default for many other operations though.
+        // There is nothing past `pc = 255`, hence `None` there.
+        let next_program_counter = self
+            .program_counter
+            .checked_add(1);
+        let mut program_counter = next_program_counter;
         let clock = self.clock + 1;
 
         let mut registers = self.registers;
@@ -82,11 +184,12 @@ impl SimulationRow {
             .memory_snapshot
             .clone();
 
-        println!(
-            "[Exec] clk: {}, pc: {}, inst: {:?}",
-            self.clock, self.program_counter, self.instruction
-        );
+        let mut return_stack = self
+            .return_stack
+            .clone();
 
+        prog.memory_config
+            .check_access(&self.instruction, self.program_counter)?;
         match self.instruction {
             Instruction::Add(a, b) => {
                 registers[usize::from(a)] = registers[usize::from(a)]
@@ -102,26 +205,48 @@ impl SimulationRow {
             }
             Instruction::Div(a, b) => {
                 registers[usize::from(a)] = registers[usize::from(a)]
-                    .wrapping_div(registers[usize::from(b)]);
+                    .checked_div(registers[usize::from(b)])
+                    .ok_or(PixieError::DivByZero {
+                        pc: self.program_counter,
+                    })?;
             }
+            // `wrapping_*` masks the amount to `0..Word::BITS`
             Instruction::Shl(reg, amount) => {
                 registers[usize::from(reg)] = registers[usize::from(reg)]
-                    .wrapping_shl(registers[usize::from(amount)].into());
+                    .wrapping_shl(u32::from(amount));
             }
             Instruction::Shr(reg, amount) => {
                 registers[usize::from(reg)] = registers[usize::from(reg)]
-                    .wrapping_shr(registers[usize::from(amount)].into());
+                    .wrapping_shr(u32::from(amount));
             }
             Instruction::Jz(reg, instloc) => {
                 if registers[usize::from(reg)] == 0 {
-                    program_counter = instloc.0
+                    program_counter = Some(instloc.0)
                 }
             }
             Instruction::Jnz(reg, instloc) => {
                 if registers[usize::from(reg)] != 0 {
-                    program_counter = instloc.0
+                    program_counter = Some(instloc.0)
                 }
             }
+            Instruction::Call(instloc) => {
+                // A `Call` at `pc = 255` has nowhere to return to
+                return_stack.push(next_program_counter.ok_or(
+                    PixieError::InstructionNotFound {
+                        pc: self.program_counter,
+                    },
+                )?);
+                program_counter = Some(instloc.0);
+            }
+            Instruction::Ret => {
+                program_counter = Some(
+                    return_stack
+                        .pop()
+                        .ok_or(PixieError::StackUnderflow {
+                            pc: self.program_counter,
+                        })?,
+                );
+            }
             Instruction::Lb(reg, memloc) => {
                 registers[usize::from(reg)] = self
                     .memory_snapshot
@@ -130,20 +255,58 @@ impl SimulationRow {
                     .unwrap_or_default(); // We treat uninitialized memory as 0
             }
             Instruction::Sb(reg, memloc) => {
-                memory_snapshot
-                    .entry(memloc.0)
-                    .and_modify(|elem| *elem = registers[usize::from(reg)])
-                    .or_insert(registers[usize::from(reg)]);
+                memory_snapshot.store(memloc.0, registers[usize::from(reg)])?;
+            }
+            Instruction::And(a, b) => {
+                registers[usize::from(a)] &= registers[usize::from(b)];
+            }
+            Instruction::Or(a, b) => {
+                registers[usize::from(a)] |= registers[usize::from(b)];
+            }
+            Instruction::Xor(a, b) => {
+                registers[usize::from(a)] ^= registers[usize::from(b)];
+            }
+            Instruction::Not(reg) => {
+                registers[usize::from(reg)] = !registers[usize::from(reg)];
+            }
+            Instruction::Li(reg, imm) => {
+                registers[usize::from(reg)] = imm;
+            }
+            Instruction::Lis(reg, imm) => {
+                registers[usize::from(reg)] = imm as i32 as Word;
+            }
+            Instruction::Mov(dst, src) => {
+                registers[usize::from(dst)] = registers[usize::from(src)];
             }
+            Instruction::Slt(a, b) => {
+                let lhs = registers[usize::from(a)] as i32;
+                let rhs = registers[usize::from(b)] as i32;
+                registers[usize::from(a)] = Word::from(lhs < rhs);
+            }
+            Instruction::Seq(a, b) => {
+                let is_equal =
+                    registers[usize::from(a)] == registers[usize::from(b)];
+                registers[usize::from(a)] = Word::from(is_equal);
+            }
+            // Changes no state, the output is read off the row, see
+            // `PreflightSimulation::outputs`
+            Instruction::Out(_) => {}
             Instruction::Halt => { // is a no-op
             }
         };
 
+        // Running past `pc = 255` is reported at the last instruction run
+        let program_counter =
+            program_counter.ok_or(PixieError::InstructionNotFound {
+                pc: self.program_counter,
+            })?;
         let instruction = prog
             .code
             .get(&program_counter)
             .cloned()
-            .context("instruction not found")?;
+            .ok_or(PixieError::InstructionNotFound {
+                pc: program_counter,
+            })?;
 
         let is_halted = instruction == Instruction::Halt;
 
@@ -154,22 +317,29 @@ impl SimulationRow {
             is_halted,
             registers,
             memory_snapshot,
+            return_stack,
         })
     }
 
     pub fn get_memory_at(
         &self,
-        address: &u8,
-    ) -> Option<u8> {
+        address: &Word,
+    ) -> Option<Word> {
         self.memory_snapshot
             .get(address)
             .copied()
     }
 
-    pub fn get_registers(&self) -> [u8; REGISTER_COUNT] {
+    pub fn get_registers(&self) -> [Word; REGISTER_COUNT] {
         self.registers
             .clone()
     }
+
+    /// Number of `Call`s not yet returned from before this row executes
+    pub fn call_depth(&self) -> u32 {
+        self.return_stack
+            .len() as u32
+    }
 }
 
 /// Unconstrainted Preflight Simulation of the program built
@@ -177,55 +347,285 @@ impl SimulationRow {
 #[derive(Debug)]
 pub struct PreflightSimulation {
     /// Memory before starting the program, a.k.a `clk = 0`
-    pub memory_init: HashMap<u8, u8>,
+    pub memory_init: HashMap<Word, Word>,
     /// Step wise execution from `clk = 1`
     pub trace_rows: Vec<SimulationRow>,
+    /// Values emitted by `Out`, in the order they were emitted
+    pub outputs: Vec<Word>,
 }
 
 impl PreflightSimulation {
-    /// Maximum number of CPU cycles allowed
-    const MAX_CPU_CYCLES_ALLOWED: usize = 1_000;
+    /// Maximum number of CPU cycles allowed by [Self::simulate]
+    pub const MAX_CPU_CYCLES_ALLOWED: usize = 1_000;
 
     /// Entry point to simulate a program and generate a `PreflightSimulation`
-    /// to be used to generate tables
-    pub fn simulate(prog: &Program) -> Result<Self> {
-        if prog
+    /// to be used to generate tables. Programs are checked with
+    /// [Program::validate] before running. A program without code is not
+    /// checked and simulates fine, but into a trivial simulation, see
+    /// [Self::is_trivial].
+    pub fn simulate(prog: &Program) -> Result<Self, PixieError> {
+        Self::simulate_with_budget(prog, Self::MAX_CPU_CYCLES_ALLOWED)
+    }
+
+    /// Like [Self::simulate], but erroring if the program does not halt
+    /// within `max_cycles` CPU cycles (i.e. trace rows) rather than
+    /// [Self::MAX_CPU_CYCLES_ALLOWED]
+    pub fn simulate_with_budget(
+        prog: &Program,
+        max_cycles: usize,
+    ) -> Result<Self, PixieError> {
+        Self::simulate_with_observer(prog, max_cycles, &mut |_| {})
+    }
+
+    /// Like [Self::simulate_with_budget], calling `observer` with every
+    /// row as it is added to the trace, i.e. once per CPU cycle. Meant for
+    /// logging or stepping through an execution as it happens.
+    pub fn simulate_with_observer(
+        prog: &Program,
+        max_cycles: usize,
+        observer: &mut dyn FnMut(&SimulationRow),
+    ) -> Result<Self, PixieError> {
+        Self::run(prog, max_cycles, false, observer)
+    }
+
+    /// Like [Self::simulate], but also erroring as soon as the machine
+    /// comes back to a state `(PC, registers, memory)` it was in before.
+    /// Execution is deterministic, so such a program never halts. Costs a
+    /// hash of the full state per cycle, which is why it is opt-in.
+    pub fn simulate_detecting_loops(
+        prog: &Program
+    ) -> Result<Self, PixieError> {
+        Self::run(prog, Self::MAX_CPU_CYCLES_ALLOWED, true, &mut |_| {})
+    }
+
+    /// Rows of the execution of `prog`, computed lazily one CPU cycle at a
+    /// time, so that a long execution need not be held in memory. Ends
+    /// with the row executing `Halt`, or with the first error, programs
+    /// that fail [Program::validate] erroring right away. Unlike
+    /// [Self::simulate], there is no cycle budget: the iterator of a
+    /// program that never halts never ends. A program without code yields
+    /// no rows.
+    pub fn rows(
+        prog: &Program
+    ) -> impl Iterator<Item = Result<SimulationRow, PixieError>> + '_ {
+        let first_row = match prog
             .code
             .is_empty()
         {
-            return Ok(Self {
-                memory_init: prog
-                    .memory_init
+            true => None,
+            false => Some(
+                prog.validate()
+                    .and_then(|()| SimulationRow::generate_first_row(prog)),
+            ),
+        };
+        std::iter::successors(first_row, move |previous| match previous {
+            Ok(row) if !row.is_halted => Some(row.execute_one_cycle(prog)),
+            _ => None,
+        })
+    }
+
+    fn run(
+        prog: &Program,
+        max_cycles: usize,
+        detect_loops: bool,
+        observer: &mut dyn FnMut(&SimulationRow),
+    ) -> Result<Self, PixieError> {
+        let mut trace_rows = Vec::with_capacity(max_cycles / 4);
+        let mut seen_states = HashSet::new();
+        let mut check_state = |row: &SimulationRow| {
+            if !detect_loops {
+                return Ok(());
+            }
+            // Memory snapshots share structure, so keeping them is cheap
+            let state = (
+                row.program_counter,
+                row.registers,
+                row.memory_snapshot
                     .clone(),
-                trace_rows: vec![],
-            });
-        }
-        let mut trace_rows =
-            Vec::with_capacity(Self::MAX_CPU_CYCLES_ALLOWED / 4);
-        let first_row = SimulationRow::generate_first_row(prog)?;
-        trace_rows.push(first_row);
+                row.return_stack
+                    .clone(),
+            );
+            match seen_states.insert(state) {
+                true => Ok(()),
+                false => Err(PixieError::NonTerminating {
+                    pc: row.program_counter,
+                }),
+            }
+        };
 
-        while trace_rows.len() <= Self::MAX_CPU_CYCLES_ALLOWED
-            && !trace_rows[trace_rows.len() - 1].is_halted
-        {
-            let current_row =
-                trace_rows[trace_rows.len() - 1].execute_one_cycle(prog)?;
-            trace_rows.push(current_row);
+        // The rows end with a halted row unless erroring
+        for row in Self::rows(prog) {
+            if trace_rows.len() >= max_cycles {
+                return Err(PixieError::CycleBudgetExceeded {
+                    budget: max_cycles,
+                });
+            }
+            let row = row?;
+            check_state(&row)?;
+            observer(&row);
+            trace_rows.push(row);
         }
 
-        if !trace_rows[trace_rows.len() - 1].is_halted {
-            return Err(anyhow!(
-                "simulation halted since MAX_CPU_CYCLES_ALLOWED reached"
-            ));
-        }
+        // Registers of a row are those before it executes, which `Out`
+        // leaves as they are
+        let outputs = trace_rows
+            .iter()
+            .filter_map(|row| match row.instruction {
+                Instruction::Out(reg) => Some(row.registers[usize::from(reg)]),
+                _ => None,
+            })
+            .collect();
 
         Ok(Self {
             memory_init: prog
                 .memory_init
                 .clone(),
             trace_rows,
+            outputs,
         })
     }
+
+    /// Number of CPU cycles the execution took, one per trace row
+    pub fn cycle_count(&self) -> u32 {
+        self.trace_rows
+            .len() as u32
+    }
+
+    /// Total gas of the execution, the [Instruction::cost] of every
+    /// executed instruction summed up
+    pub fn gas_used(&self) -> u64 {
+        self.trace_rows
+            .iter()
+            .map(|row| {
+                row.instruction
+                    .cost()
+            })
+            .sum()
+    }
+
+    /// Whether nothing was executed, i.e. the program had no code. Tables
+    /// of a trivial simulation are all padding: a proof over them proves
+    /// nothing about any execution and should not be mistaken for one.
+    pub fn is_trivial(&self) -> bool {
+        self.trace_rows
+            .is_empty()
+    }
+
+    /// `(clock, value)` for every point in the execution at which the
+    /// value at `address` changes. Initialized memory shows up at clock
+    /// `0`, a store shows up at the clock of the row executing it.
+    pub fn address_timeline(
+        &self,
+        address: Word,
+    ) -> Vec<(u32, Word)> {
+        let mut current = self
+            .memory_init
+            .get(&address)
+            .copied();
+        let mut timeline = current
+            .map(|value| vec![(0, value)])
+            .unwrap_or_default();
+
+        // Memory after a row executes is the snapshot of the next row
+        for rows in self
+            .trace_rows
+            .windows(2)
+        {
+            let value = rows[1].get_memory_at(&address);
+            if value != current {
+                if let Some(value) = value {
+                    timeline.push((rows[0].clock, value));
+                }
+                current = value;
+            }
+        }
+        timeline
+    }
+
+    /// Deterministic, human readable rendering of the full execution:
+    /// the initial memory followed by one line per row. Memory is always
+    /// listed sorted by address. Meant to be checked in and diffed, see
+    /// `fixtures::assert_golden`.
+    pub fn to_golden_string(&self) -> String {
+        let render_memory = |mut cells: Vec<(&Word, &Word)>| {
+            cells.sort();
+            cells
+                .into_iter()
+                .map(|(addr, value)| format!("{:#04x}={:#04x}", addr, value))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        let mut golden = format!(
+            "memory_init: [{}]\n",
+            render_memory(
+                self.memory_init
+                    .iter()
+                    .collect()
+            ),
+        );
+        for row in &self.trace_rows {
+            golden.push_str(&format!(
+                "clk={} pc={} inst={:?} regs={:?} halted={} memory=[{}]\n",
+                row.clock,
+                row.program_counter,
+                row.instruction,
+                row.registers,
+                row.is_halted,
+                render_memory(
+                    row.memory_snapshot
+                        .iter()
+                        .collect()
+                ),
+            ));
+        }
+        golden
+    }
+
+    /// Machine readable rendering of the execution: a JSON array with one
+    /// object per row, holding `clock`, `program_counter`, `instruction`
+    /// (in its `Debug` form), `registers` and `memory`. Like
+    /// [Self::to_golden_string], `memory` is the snapshot of the row keyed
+    /// by hex address in sorted order, so the output is deterministic.
+    pub fn export_trace_json(&self) -> String {
+        let rows = self
+            .trace_rows
+            .iter()
+            .map(|row| {
+                let mut cells = row
+                    .memory_snapshot
+                    .iter()
+                    .collect::<Vec<(&Word, &Word)>>();
+                cells.sort();
+                let memory = cells
+                    .into_iter()
+                    .map(|(addr, value)| {
+                        format!("\"{:#04x}\": {}", addr, value)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let registers = row
+                    .registers
+                    .map(|reg| reg.to_string())
+                    .join(", ");
+                // `Debug` of an instruction holds no `"` or `\` to escape
+                format!(
+                    "  {{\"clock\": {}, \"program_counter\": {}, \
+                     \"instruction\": \"{:?}\", \"registers\": [{}], \
+                     \"memory\": {{{}}}}}",
+                    row.clock,
+                    row.program_counter,
+                    row.instruction,
+                    registers,
+                    memory,
+                )
+            })
+            .collect::<Vec<String>>();
+        match rows.is_empty() {
+            true => "[]\n".to_string(),
+            false => format!("[\n{}\n]\n", rows.join(",\n")),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -233,40 +633,33 @@ mod tests {
     use super::*;
     use std::collections::HashMap;
 
-    use crate::vm_specs::{
-        Instruction,
-        InstructionLocation,
-        MemoryLocation,
-        Program,
-        Register,
+    use crate::{
+        fixtures,
+        vm_specs::{
+            Instruction,
+            InstructionLocation,
+            MemoryConfig,
+            MemoryLocation,
+            Program,
+            ProgramBuilder,
+            Register,
+        },
     };
 
     #[test]
     /// Tests whether two numbers in memory can be added together
     /// in the ZKVM
     fn test_preflight_add_memory() {
-        let instructions = vec![
-            Instruction::Lb(Register::R0, MemoryLocation(0x40)),
-            Instruction::Lb(Register::R1, MemoryLocation(0x41)),
-            Instruction::Add(Register::R0, Register::R1),
-            Instruction::Sb(Register::R0, MemoryLocation(0x42)),
-            Instruction::Halt,
-        ];
-
-        let code = instructions
-            .into_iter()
-            .enumerate()
-            .map(|(idx, inst)| (idx as u8, inst))
-            .collect::<HashMap<u8, Instruction>>();
-
-        let memory_init: HashMap<u8, u8> =
-            HashMap::from_iter(vec![(0x40, 0x20), (0x41, 0x45)]);
-
-        let program = Program {
-            entry_point: 0,
-            code,
-            memory_init,
-        };
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0x20)
+            .memory(0x41, 0x45)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x41)))
+            .instruction(Instruction::Add(Register::R0, Register::R1))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
 
         let expected = (0x42, 0x65);
 
@@ -285,57 +678,837 @@ mod tests {
         );
     }
 
+    #[test]
+    /// Two loads, an add, a store and a halt: `2 + 2 + 1 + 2 + 0`
+    fn test_gas_used() {
+        let simulation =
+            PreflightSimulation::simulate(&fixtures::add()).unwrap();
+        assert_eq!(simulation.cycle_count(), 5);
+        assert_eq!(simulation.gas_used(), 7);
+
+        // Three rounds of `Sub`, `Sb` and `Jnz` after the two loads
+        let simulation =
+            PreflightSimulation::simulate(&fixtures::simple_loop()).unwrap();
+        assert_eq!(simulation.cycle_count(), 2 + 3 * 3 + 1);
+        assert_eq!(simulation.gas_used(), 2 * 2 + 3 * (1 + 2 + 1));
+    }
+
     #[test]
     /// Tests whether execution stops on reaching `MAX_CPU_CYCLES_ALLOWED`
     fn test_max_cpu_cycles() {
-        let instructions = vec![
-            Instruction::Jz(Register::R0, InstructionLocation(0x00)),
-            Instruction::Halt,
-        ];
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Jz(
+                Register::R0,
+                InstructionLocation(0x00),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert_eq!(
+            simulation.unwrap_err(),
+            PixieError::CycleBudgetExceeded {
+                budget: PreflightSimulation::MAX_CPU_CYCLES_ALLOWED
+            }
+        );
+    }
 
-        let code = instructions
-            .into_iter()
-            .enumerate()
-            .map(|(idx, inst)| (idx as u8, inst))
-            .collect::<HashMap<u8, Instruction>>();
+    #[test]
+    /// The `Jz R0, 0` self-loop comes back to its very first state on the
+    /// second cycle, long before the budget runs out
+    fn test_non_terminating() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Jz(Register::R0, InstructionLocation(0)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+        assert_eq!(
+            PreflightSimulation::simulate_detecting_loops(&program)
+                .unwrap_err(),
+            PixieError::NonTerminating { pc: 0 }
+        );
+
+        // Loops which change state on every round are not cut short
+        let program = fixtures::countdown(5);
+        let simulation =
+            PreflightSimulation::simulate_detecting_loops(&program).unwrap();
+        assert_eq!(simulation.trace_rows.len(), 2 * 5 + 4);
+    }
+
+    #[test]
+    /// A countdown loop taking `Jnz` back until `R0` reaches zero, then
+    /// falling through to `Halt`
+    fn test_countdown_jnz() {
+        let simulation =
+            PreflightSimulation::simulate(&fixtures::countdown(5)).unwrap();
+        assert_eq!(simulation.trace_rows.len(), 2 * 5 + 4);
+
+        // `Jnz` at `pc = 4` jumps back to `pc = 3` four times, the fifth
+        // time `R0` is zero and execution falls through to `pc = 5`
+        let jump_targets = simulation
+            .trace_rows
+            .windows(2)
+            .filter(|rows| rows[0].program_counter == 4)
+            .map(|rows| rows[1].program_counter)
+            .collect::<Vec<u8>>();
+        assert_eq!(jump_targets, [3, 3, 3, 3, 5]);
+
+        let last_row = simulation
+            .trace_rows
+            .last()
+            .unwrap();
+        assert!(last_row.is_halted);
+        assert_eq!(last_row.get_registers(), [0, 1, 0, 0]);
+    }
+
+    #[test]
+    /// The JSON export of the add program parses, with one object per row
+    fn test_export_trace_json() {
+        let simulation =
+            PreflightSimulation::simulate(&fixtures::add()).unwrap();
+        let json = simulation.export_trace_json();
+        assert_eq!(json, simulation.export_trace_json());
+
+        let rows: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let rows = rows
+            .as_array()
+            .unwrap();
+        assert_eq!(rows.len(), simulation.trace_rows.len());
+        assert_eq!(rows[0]["instruction"], "Lb(R0, MemoryLocation(64))");
+
+        let last_row = rows
+            .last()
+            .unwrap();
+        assert_eq!(last_row["clock"], 5);
+        assert_eq!(last_row["program_counter"], 4);
+        assert_eq!(
+            last_row["registers"],
+            serde_json::json!([0x65, 0x45, 0, 0])
+        );
+        assert_eq!(last_row["memory"]["0x42"], 0x65);
+    }
+
+    #[test]
+    /// The observer sees every row, in order, as it is executed
+    fn test_simulate_with_observer() {
+        let mut events = vec![];
+        let simulation = PreflightSimulation::simulate_with_observer(
+            &fixtures::countdown(3),
+            PreflightSimulation::MAX_CPU_CYCLES_ALLOWED,
+            &mut |row| {
+                events.push(format!(
+                    "clk: {}, pc: {}, inst: {:?}",
+                    row.clock, row.program_counter, row.instruction
+                ))
+            },
+        )
+        .unwrap();
+
+        assert_eq!(events.len(), simulation.trace_rows.len());
+        assert_eq!(events[0], "clk: 1, pc: 0, inst: Li(R0, 3)");
+        assert_eq!(
+            events
+                .last()
+                .unwrap(),
+            "clk: 10, pc: 5, inst: Halt"
+        );
+    }
+
+    #[test]
+    /// The lazy rows are those of `simulate`, ending at `Halt`
+    fn test_rows() {
+        let program = fixtures::countdown(5);
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let rows = PreflightSimulation::rows(&program)
+            .collect::<Result<Vec<SimulationRow>, PixieError>>()
+            .unwrap();
+        assert_eq!(rows.len(), simulation.trace_rows.len());
+
+        let (last_row, expected) = (
+            rows.last()
+                .unwrap(),
+            simulation
+                .trace_rows
+                .last()
+                .unwrap(),
+        );
+        assert!(last_row.is_halted);
+        assert_eq!(last_row.instruction, Instruction::Halt);
+        assert_eq!(last_row.clock, expected.clock);
+        assert_eq!(last_row.get_registers(), expected.get_registers());
+        assert_eq!(last_row.memory_snapshot, expected.memory_snapshot);
+
+        // Only as many rows as asked for are computed, even for a program
+        // that never halts
+        let looping = ProgramBuilder::new()
+            .instruction(Instruction::Jz(Register::R0, InstructionLocation(0)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+        assert_eq!(
+            PreflightSimulation::rows(&looping)
+                .take(5000)
+                .filter(Result::is_ok)
+                .count(),
+            5000
+        );
 
+        // Invalid programs error right away, then stop
         let program = Program {
-            entry_point: 0,
-            code,
-            ..Default::default()
+            entry_point: 0x10,
+            ..fixtures::add()
         };
+        let rows = PreflightSimulation::rows(&program).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0]
+                .as_ref()
+                .unwrap_err(),
+            &PixieError::EntryPointMissing
+        );
+    }
 
-        let simulation = PreflightSimulation::simulate(&program);
-        assert!(simulation.is_err());
+    #[test]
+    /// The cycle budget is configurable, and errors report the one hit
+    fn test_cycle_budget() {
+        // Executes in exactly `2 * 23 + 4 = 50` cycles
+        let program = fixtures::countdown(23);
+
+        let simulation =
+            PreflightSimulation::simulate_with_budget(&program, 10);
+        assert_eq!(
+            simulation
+                .unwrap_err()
+                .to_string(),
+            "simulation halted since the budget of 10 CPU cycles was reached"
+        );
+        assert!(
+            PreflightSimulation::simulate_with_budget(&program, 49).is_err()
+        );
+
+        for budget in [50, 100] {
+            let simulation =
+                PreflightSimulation::simulate_with_budget(&program, budget);
+            assert_eq!(
+                simulation
+                    .unwrap()
+                    .trace_rows
+                    .len(),
+                50
+            );
+        }
     }
 
     #[test]
     /// Tests whether execution halts
     fn test_haltable() {
-        let instructions = vec![
-            Instruction::Lb(Register::R0, MemoryLocation(0x40)),
-            Instruction::Lb(Register::R1, MemoryLocation(0x41)),
-            Instruction::Sub(Register::R0, Register::R1),
-            Instruction::Jnz(Register::R0, InstructionLocation(0x02)),
-            Instruction::Halt,
-        ];
-
-        let code = instructions
-            .into_iter()
-            .enumerate()
-            .map(|(idx, inst)| (idx as u8, inst))
-            .collect::<HashMap<u8, Instruction>>();
-
-        let memory_init: HashMap<u8, u8> =
-            HashMap::from_iter(vec![(0x40, 0x05), (0x41, 0x01)]);
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0x05)
+            .memory(0x41, 0x01)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x41)))
+            .instruction(Instruction::Sub(Register::R0, Register::R1))
+            .instruction(Instruction::Jnz(
+                Register::R0,
+                InstructionLocation(0x02),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
 
-        let program = Program {
-            entry_point: 0,
-            code,
-            memory_init,
-        };
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+    }
+
+    /// Runs `inst` on `R0 = 0b1100_1010` and `R1 = 0b1010_0110` and returns
+    /// the registers after it
+    fn registers_after(inst: Instruction) -> [Word; REGISTER_COUNT] {
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0b1100_1010)
+            .memory(0x41, 0b1010_0110)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x41)))
+            .instruction(inst)
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        simulation
+            .unwrap()
+            .trace_rows
+            .last()
+            .unwrap()
+            .get_registers()
+    }
+
+    #[test]
+    /// Tests the bitwise instructions
+    fn test_bitwise_ops() {
+        assert_eq!(
+            registers_after(Instruction::And(Register::R0, Register::R1)),
+            [0b1000_0010, 0b1010_0110, 0, 0]
+        );
+        assert_eq!(
+            registers_after(Instruction::Or(Register::R0, Register::R1)),
+            [0b1110_1110, 0b1010_0110, 0, 0]
+        );
+        assert_eq!(
+            registers_after(Instruction::Xor(Register::R1, Register::R0)),
+            [0b1100_1010, 0b0110_1100, 0, 0]
+        );
+        assert_eq!(
+            registers_after(Instruction::Not(Register::R0)),
+            [!0b1100_1010, 0b1010_0110, 0, 0]
+        );
+    }
+
+    #[test]
+    /// Shift amounts are immediates, masked to `0..Word::BITS`
+    fn test_shift_immediate() {
+        // `R0 = 0b1100_1010`
+        let shifted = |inst: Instruction| registers_after(inst)[0];
+        let value: Word = 0b1100_1010;
+
+        assert_eq!(shifted(Instruction::Shl(Register::R0, 0)), value);
+        assert_eq!(shifted(Instruction::Shr(Register::R0, 0)), value);
+
+        assert_eq!(shifted(Instruction::Shl(Register::R0, 3)), value << 3);
+        assert_eq!(shifted(Instruction::Shr(Register::R0, 3)), value >> 3);
+        assert_eq!(shifted(Instruction::Shl(Register::R0, 31)), 0);
+
+        let over_wide = Word::BITS as u8 + 3;
+        let (shl, shr) = (Instruction::Shl, Instruction::Shr);
+        assert_eq!(shifted(shl(Register::R0, over_wide)), value << 3);
+        assert_eq!(shifted(shr(Register::R0, over_wide)), value >> 3);
+        assert_eq!(
+            shifted(Instruction::Shl(Register::R0, Word::BITS as u8)),
+            value
+        );
+    }
+
+    #[test]
+    /// Tests that an immediate can be loaded and stored to memory
+    fn test_load_immediate() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R1, 0x2a))
+            .instruction(Instruction::Sb(Register::R1, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        let simulation = simulation.unwrap();
+        let last_row = simulation
+            .trace_rows
+            .last()
+            .unwrap();
+        assert_eq!(last_row.get_registers(), [0, 0x2a, 0, 0]);
+        assert_eq!(last_row.get_memory_at(&0x42), Some(0x2a));
+    }
+
+    #[test]
+    /// Tests that a signed immediate is sign-extended to a word, which
+    /// `Slt` then reads back as the same negative number
+    fn test_load_signed_immediate() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Lis(Register::R0, -1))
+            .instruction(Instruction::Lis(Register::R1, 0x2a))
+            .instruction(Instruction::Lis(Register::R2, -1))
+            .instruction(Instruction::Slt(Register::R2, Register::R1))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        let simulation = simulation.unwrap();
+        let last_row = simulation
+            .trace_rows
+            .last()
+            .unwrap();
+        assert_eq!(last_row.get_registers(), [0xffff_ffff, 0x2a, 1, 0]);
+    }
+
+    #[test]
+    /// Two bytes loaded from memory are added and the sum is output
+    fn test_out() {
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0x20)
+            .memory(0x41, 0x45)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x41)))
+            .instruction(Instruction::Add(Register::R0, Register::R1))
+            .instruction(Instruction::Out(Register::R0))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        assert_eq!(simulation.outputs, vec![0x65]);
+        assert!(PreflightSimulation::simulate(&fixtures::add())
+            .unwrap()
+            .outputs
+            .is_empty());
+    }
+
+    #[test]
+    /// Tests that `Mov` copies a loaded value into another register,
+    /// leaving the source as it was
+    fn test_mov() {
+        let program = ProgramBuilder::new()
+            .memory(0x40, 0x2a)
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(0x40)))
+            .instruction(Instruction::Mov(Register::R0, Register::R1))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        let simulation = simulation.unwrap();
+        let last_row = simulation
+            .trace_rows
+            .last()
+            .unwrap();
+        assert_eq!(last_row.get_registers(), [0x2a, 0x2a, 0, 0]);
+        assert_eq!(last_row.get_memory_at(&0x42), Some(0x2a));
+    }
+
+    /// Runs `inst` on `R0 = lhs` and `R1 = rhs` and returns `R0` after it
+    fn compare(
+        inst: Instruction,
+        lhs: Word,
+        rhs: Word,
+    ) -> Word {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, lhs))
+            .instruction(Instruction::Li(Register::R1, rhs))
+            .instruction(inst)
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert!(simulation.is_ok());
+        simulation
+            .unwrap()
+            .trace_rows
+            .last()
+            .unwrap()
+            .get_registers()[0]
+    }
+
+    #[test]
+    /// `Slt` compares words as two's-complement `i32`
+    fn test_set_less_than() {
+        let slt = Instruction::Slt(Register::R0, Register::R1);
+        let minus_one = -1i32 as Word;
+        let min = i32::MIN as Word;
+
+        // Negative
+        assert_eq!(compare(slt.clone(), minus_one, 1), 1);
+        assert_eq!(compare(slt.clone(), 1, minus_one), 0);
+        assert_eq!(compare(slt.clone(), min, minus_one), 1);
+        assert_eq!(compare(slt.clone(), minus_one, minus_one), 0);
+
+        // Zero
+        assert_eq!(compare(slt.clone(), 0, 0), 0);
+        assert_eq!(compare(slt.clone(), minus_one, 0), 1);
+        assert_eq!(compare(slt.clone(), 0, minus_one), 0);
+
+        // Positive, up to the largest `i32`
+        assert_eq!(compare(slt.clone(), 0, 1), 1);
+        assert_eq!(compare(slt.clone(), 0x2a, 0x29), 0);
+        assert_eq!(compare(slt, i32::MAX as Word, min), 0);
+    }
+
+    #[test]
+    /// `Seq` compares words bit for bit
+    fn test_set_equal() {
+        let seq = Instruction::Seq(Register::R0, Register::R1);
+        let minus_one = -1i32 as Word;
+
+        assert_eq!(compare(seq.clone(), minus_one, minus_one), 1);
+        assert_eq!(compare(seq.clone(), minus_one, 1), 0);
+        assert_eq!(compare(seq.clone(), 0, 0), 1);
+        assert_eq!(compare(seq.clone(), 0, minus_one), 0);
+        assert_eq!(compare(seq.clone(), 0x2a, 0x2a), 1);
+        assert_eq!(compare(seq, 0x2a, 0x2b), 0);
+    }
+
+    #[test]
+    /// A jump to a location holding no instruction is rejected before
+    /// anything runs, even if the jump would never be taken
+    fn test_dangling_jump_target() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0x00))
+            .instruction(Instruction::Jnz(
+                Register::R0,
+                InstructionLocation(0x10),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert_eq!(
+            simulation.unwrap_err(),
+            PixieError::JumpTargetMissing {
+                pc: 0x01,
+                target: 0x10
+            }
+        );
+    }
+
+    #[test]
+    /// A program which can never reach a `Halt` is rejected before anything
+    /// runs, rather than after exhausting the cycle budget
+    fn test_no_halt() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0x01))
+            .instruction(Instruction::Jnz(
+                Register::R0,
+                InstructionLocation(0x00),
+            ))
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert_eq!(simulation.unwrap_err(), PixieError::HaltUnreachable);
+    }
+
+    #[test]
+    /// Dividing by zero fails the simulation, pointing at the `Div`
+    fn test_division_by_zero() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0x2a))
+            .instruction(Instruction::Li(Register::R1, 0x02))
+            .instruction(Instruction::Div(Register::R0, Register::R1))
+            .instruction(Instruction::Div(Register::R0, Register::R2))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program);
+        assert_eq!(simulation.unwrap_err(), PixieError::DivByZero { pc: 3 });
+    }
+
+    #[test]
+    /// `main` calls a subroutine doubling `R0`, which returns right after
+    /// the `Call`
+    fn test_call_and_return() {
+        let program = ProgramBuilder::new()
+            .strict()
+            .instruction(Instruction::Li(Register::R0, 0x15))
+            .instruction(Instruction::Call(InstructionLocation(4)))
+            .instruction(Instruction::Out(Register::R0))
+            .instruction(Instruction::Halt)
+            // Subroutine doubling `R0`
+            .instruction(Instruction::Add(Register::R0, Register::R0))
+            .instruction(Instruction::Ret)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let pcs = simulation
+            .trace_rows
+            .iter()
+            .map(|row| row.program_counter)
+            .collect::<Vec<u8>>();
+        assert_eq!(pcs, vec![0, 1, 4, 5, 2, 3]);
+
+        let after_return = &simulation.trace_rows[4];
+        assert_eq!(after_return.program_counter, 2);
+        assert_eq!(after_return.get_registers()[0], 0x2a);
+        assert_eq!(simulation.outputs, vec![0x2a]);
+    }
+
+    #[test]
+    /// The call depth counts the `Call`s not yet returned from, and is
+    /// back to zero once every `Call` returned
+    fn test_call_depth() {
+        let program = ProgramBuilder::new()
+            .strict()
+            .instruction(Instruction::Call(InstructionLocation(2)))
+            .instruction(Instruction::Halt)
+            .instruction(Instruction::Call(InstructionLocation(4)))
+            .instruction(Instruction::Ret)
+            .instruction(Instruction::Ret)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let depths = simulation
+            .trace_rows
+            .iter()
+            .map(SimulationRow::call_depth)
+            .collect::<Vec<u32>>();
+        assert_eq!(depths, vec![0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    /// Running past `pc = 255`, or calling from there, fails the simulation
+    /// rather than wrapping the program counter around
+    fn test_program_counter_overflow() {
+        let jz = Instruction::Jz(Register::R0, InstructionLocation(0xff));
+        for last in [
+            Instruction::Add(Register::R0, Register::R0),
+            Instruction::Call(InstructionLocation(2)),
+        ] {
+            let program = Program {
+                code: HashMap::from([
+                    (0x00, Instruction::Li(Register::R0, 0)),
+                    (0x01, jz.clone()),
+                    (0x02, Instruction::Halt),
+                    (0xff, last),
+                ]),
+                ..Default::default()
+            };
+            assert!(program
+                .validate()
+                .is_ok());
+
+            let simulation = PreflightSimulation::simulate(&program);
+            assert_eq!(
+                simulation.unwrap_err(),
+                PixieError::InstructionNotFound { pc: 0xff }
+            );
+        }
+    }
+
+    #[test]
+    /// Returning with no `Call` to return from fails the simulation,
+    /// pointing at the `Ret`
+    fn test_stack_underflow() {
+        let program = ProgramBuilder::new()
+            .strict()
+            .instruction(Instruction::Li(Register::R0, 0))
+            .instruction(Instruction::Jz(Register::R0, InstructionLocation(3)))
+            .instruction(Instruction::Halt)
+            .instruction(Instruction::Ret)
+            .build()
+            .unwrap();
 
         let simulation = PreflightSimulation::simulate(&program);
+        assert_eq!(
+            simulation.unwrap_err(),
+            PixieError::StackUnderflow { pc: 3 }
+        );
+    }
+
+    #[test]
+    /// Tests that values past a byte are held in full, both in registers
+    /// and in memory at an address past a byte
+    fn test_word_width() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0xff))
+            .instruction(Instruction::Li(Register::R1, 0x1_0001))
+            .instruction(Instruction::Add(Register::R0, Register::R1))
+            .instruction(Instruction::Sb(
+                Register::R0,
+                MemoryLocation(0x1_0000),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let last_row = simulation
+            .trace_rows
+            .last()
+            .unwrap();
+        assert_eq!(last_row.get_registers(), [0x1_0100, 0x1_0001, 0, 0]);
+        assert_eq!(last_row.get_memory_at(&0x1_0000), Some(0x1_0100));
+    }
+
+    #[test]
+    /// Runs a 900 cycle store loop over a fully initialized memory. Rows
+    /// which do not store share their snapshot with the previous row
+    /// instead of copying every cell.
+    fn test_memory_snapshot_sharing() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 300))
+            .instruction(Instruction::Li(Register::R1, 1))
+            .instruction(Instruction::Sub(Register::R0, Register::R1))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(0x42)))
+            .instruction(Instruction::Jnz(
+                Register::R0,
+                InstructionLocation(0x02),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap()
+            .with_memory_image([0xaa; 256], true);
+
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        assert_eq!(simulation.trace_rows.len(), 2 + 3 * 300 + 1);
+
+        let (mut shared, mut copied) = (0, 0);
+        for rows in simulation
+            .trace_rows
+            .windows(2)
+        {
+            if rows[0]
+                .memory_snapshot
+                .ptr_eq(&rows[1].memory_snapshot)
+            {
+                shared += 1;
+            } else {
+                assert!(matches!(rows[0].instruction, Instruction::Sb(_, _)));
+                copied += 1;
+            }
+        }
+        // Only the 300 stores touch memory
+        assert_eq!(copied, 300);
+        assert_eq!(shared, simulation.trace_rows.len() - 1 - 300);
+    }
+
+    /// Memory holding at most two cells
+    #[derive(Default)]
+    struct TwoCellMemory(HashMap<Word, Word>);
+
+    impl Memory for TwoCellMemory {
+        fn capacity(&self) -> usize {
+            2
+        }
+
+        fn cells(&self) -> usize {
+            self.0
+                .len()
+        }
+
+        fn load(
+            &self,
+            address: Word,
+        ) -> Option<Word> {
+            self.0
+                .load(address)
+        }
+
+        fn insert(
+            &mut self,
+            address: Word,
+            value: Word,
+        ) {
+            self.0
+                .insert(address, value);
+        }
+    }
+
+    #[test]
+    fn test_memory_capacity() {
+        // Qualified, as `HashMap` has an inherent `capacity` too
+        assert_eq!(
+            Memory::capacity(&HashMap::<Word, Word>::new()),
+            MEMORY_CAPACITY
+        );
+
+        let mut memory = TwoCellMemory::default();
+        assert!(memory
+            .store(0x40, 1)
+            .is_ok());
+        assert!(memory
+            .store(0x41, 2)
+            .is_ok());
+        // Overwriting needs no new cell
+        assert!(memory
+            .store(0x40, 3)
+            .is_ok());
+        assert!(memory
+            .store(0x42, 4)
+            .is_err());
+        assert_eq!(memory.cells(), 2);
+        assert_eq!(memory.load(0x40), Some(3));
+    }
+
+    /// Stores `0x2a` to `addr` and loads it back, with `0x00..=0x3f` read
+    /// only and `0x40..=0x7f` writable
+    fn store_and_load(addr: Word) -> Result<PreflightSimulation, PixieError> {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0x2a))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(addr)))
+            .instruction(Instruction::Lb(Register::R1, MemoryLocation(addr)))
+            .instruction(Instruction::Halt)
+            .memory_config(MemoryConfig {
+                readable: 0x00..=0x7f,
+                writable: 0x40..=0x7f,
+            })
+            .build()
+            .unwrap();
+        PreflightSimulation::simulate(&program)
+    }
+
+    #[test]
+    /// Stores succeed only into the writable region
+    fn test_memory_access_violation() {
+        let simulation = store_and_load(0x40).unwrap();
+        assert_eq!(
+            simulation
+                .trace_rows
+                .last()
+                .unwrap()
+                .registers[1],
+            0x2a
+        );
+
+        // Read only
+        assert_eq!(
+            store_and_load(0x3f).unwrap_err(),
+            PixieError::MemoryAccessViolation { addr: 0x3f, pc: 1 }
+        );
+        // Neither readable nor writable
+        assert_eq!(
+            store_and_load(0x80).unwrap_err(),
+            PixieError::MemoryAccessViolation { addr: 0x80, pc: 1 }
+        );
+
+        // Loads outside of the readable region fail too
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x80)))
+            .instruction(Instruction::Halt)
+            .memory_config(MemoryConfig {
+                readable: 0x00..=0x7f,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(
+            PreflightSimulation::simulate(&program).unwrap_err(),
+            PixieError::MemoryAccessViolation { addr: 0x80, pc: 0 }
+        );
+    }
+
+    #[test]
+    fn test_is_trivial() {
+        let simulation = PreflightSimulation::simulate(&Program::default());
         assert!(simulation.is_ok());
+        assert!(simulation
+            .unwrap()
+            .is_trivial());
+
+        let simulation =
+            PreflightSimulation::simulate(&fixtures::add()).unwrap();
+        assert!(!simulation.is_trivial());
+    }
+
+    #[test]
+    /// Tests that the timeline of an address only records its changes
+    fn test_address_timeline() {
+        let simulation =
+            PreflightSimulation::simulate(&fixtures::add()).unwrap();
+
+        // Stored once by the `Sb` at `clk = 4`
+        assert_eq!(simulation.address_timeline(0x42), vec![(4, 0x65)]);
+        // Only read, hence only the initial value
+        assert_eq!(simulation.address_timeline(0x40), vec![(0, 0x20)]);
+        // Never touched
+        assert!(simulation
+            .address_timeline(0x43)
+            .is_empty());
     }
 }
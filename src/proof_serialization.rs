@@ -0,0 +1,356 @@
+//! Serialization of [PixieProof], behind the `serde` feature. `starky`
+//! proofs do not implement serde themselves, so a [PixieProof] goes
+//! through [ProofData], which holds the same values in types that do:
+//! merkle caps, FRI proofs and field elements.
+
+use anyhow::{
+    anyhow,
+    Result,
+};
+use plonky2::{
+    field::extension::Extendable,
+    fri::proof::FriProof,
+    hash::{
+        hash_types::RichField,
+        hashing::PlonkyPermutation,
+        merkle_tree::MerkleCap,
+    },
+    plonk::config::{
+        GenericConfig,
+        Hasher,
+    },
+};
+use serde::{
+    de::Error,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use starky::{
+    lookup::{
+        GrandProductChallenge,
+        GrandProductChallengeSet,
+    },
+    proof::{
+        MultiProof,
+        StarkOpeningSet,
+        StarkProof,
+        StarkProofWithMetadata,
+    },
+};
+
+use crate::stark_pixie_zkvm::{
+    PixieProof,
+    NUM_TABLES,
+};
+
+/// State of the transcript a table proof was started from
+type Permutation<F, C, const D: usize> =
+    <<C as GenericConfig<D>>::Hasher as Hasher<F>>::Permutation;
+
+/// Serializable counterpart of a [StarkProofWithMetadata]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct TableProofData<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    trace_cap: MerkleCap<F, C::Hasher>,
+    auxiliary_polys_cap: Option<MerkleCap<F, C::Hasher>>,
+    quotient_polys_cap: Option<MerkleCap<F, C::Hasher>>,
+    local_values: Vec<F::Extension>,
+    next_values: Vec<F::Extension>,
+    auxiliary_polys: Option<Vec<F::Extension>>,
+    auxiliary_polys_next: Option<Vec<F::Extension>>,
+    ctl_zs_first: Option<Vec<F>>,
+    quotient_polys: Option<Vec<F::Extension>>,
+    opening_proof: FriProof<F, C::Hasher, D>,
+    init_challenger_state: Vec<F>,
+}
+
+impl<F, C, const D: usize> From<StarkProofWithMetadata<F, C, D>>
+    for TableProofData<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn from(stark_proof: StarkProofWithMetadata<F, C, D>) -> Self {
+        let StarkProofWithMetadata {
+            init_challenger_state,
+            proof,
+        } = stark_proof;
+        let StarkProof {
+            trace_cap,
+            auxiliary_polys_cap,
+            quotient_polys_cap,
+            openings,
+            opening_proof,
+        } = proof;
+        let StarkOpeningSet {
+            local_values,
+            next_values,
+            auxiliary_polys,
+            auxiliary_polys_next,
+            ctl_zs_first,
+            quotient_polys,
+        } = openings;
+        Self {
+            trace_cap,
+            auxiliary_polys_cap,
+            quotient_polys_cap,
+            local_values,
+            next_values,
+            auxiliary_polys,
+            auxiliary_polys_next,
+            ctl_zs_first,
+            quotient_polys,
+            opening_proof,
+            init_challenger_state: init_challenger_state
+                .as_ref()
+                .to_vec(),
+        }
+    }
+}
+
+impl<F, C, const D: usize> TryFrom<TableProofData<F, C, D>>
+    for StarkProofWithMetadata<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(data: TableProofData<F, C, D>) -> Result<Self> {
+        let width = Permutation::<F, C, D>::WIDTH;
+        if data
+            .init_challenger_state
+            .len()
+            != width
+        {
+            return Err(anyhow!(
+                "challenger state of {} elements, expected {}",
+                data.init_challenger_state
+                    .len(),
+                width
+            ));
+        }
+        Ok(Self {
+            init_challenger_state: Permutation::<F, C, D>::new(
+                data.init_challenger_state,
+            ),
+            proof: StarkProof {
+                trace_cap: data.trace_cap,
+                auxiliary_polys_cap: data.auxiliary_polys_cap,
+                quotient_polys_cap: data.quotient_polys_cap,
+                openings: StarkOpeningSet {
+                    local_values: data.local_values,
+                    next_values: data.next_values,
+                    auxiliary_polys: data.auxiliary_polys,
+                    auxiliary_polys_next: data.auxiliary_polys_next,
+                    ctl_zs_first: data.ctl_zs_first,
+                    quotient_polys: data.quotient_polys,
+                },
+                opening_proof: data.opening_proof,
+            },
+        })
+    }
+}
+
+/// Serializable counterpart of a [PixieProof]. Tables are indexed by
+/// `Table` like in the proof, and each CTL challenge is `(beta, gamma)`.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct ProofData<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    stark_proofs: Vec<TableProofData<F, C, D>>,
+    public_inputs: Vec<Vec<F>>,
+    ctl_challenges: Vec<(F, F)>,
+}
+
+impl<F, C, const D: usize> From<PixieProof<F, C, D>> for ProofData<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn from(proof: PixieProof<F, C, D>) -> Self {
+        Self {
+            stark_proofs: proof
+                .multi_proof
+                .stark_proofs
+                .into_iter()
+                .map(TableProofData::from)
+                .collect(),
+            public_inputs: proof
+                .public_inputs
+                .into_iter()
+                .collect(),
+            ctl_challenges: proof
+                .ctl_challenges
+                .challenges
+                .into_iter()
+                .map(|challenge| (challenge.beta, challenge.gamma))
+                .collect(),
+        }
+    }
+}
+
+impl<F, C, const D: usize> TryFrom<ProofData<F, C, D>> for PixieProof<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(data: ProofData<F, C, D>) -> Result<Self> {
+        let stark_proofs = data
+            .stark_proofs
+            .into_iter()
+            .map(StarkProofWithMetadata::try_from)
+            .collect::<Result<Vec<_>>>()?
+            .try_into()
+            .map_err(|proofs: Vec<_>| {
+                anyhow!(
+                    "{} table proofs, expected {}",
+                    proofs.len(),
+                    NUM_TABLES
+                )
+            })?;
+        let public_inputs = data
+            .public_inputs
+            .try_into()
+            .map_err(|inputs: Vec<_>| {
+                anyhow!(
+                    "public inputs of {} tables, expected {}",
+                    inputs.len(),
+                    NUM_TABLES
+                )
+            })?;
+        let challenges = data
+            .ctl_challenges
+            .into_iter()
+            .map(|(beta, gamma)| GrandProductChallenge { beta, gamma })
+            .collect();
+        Ok(Self {
+            multi_proof: MultiProof { stark_proofs },
+            public_inputs,
+            ctl_challenges: GrandProductChallengeSet { challenges },
+        })
+    }
+}
+
+impl<F, C, const D: usize> Serialize for PixieProof<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ProofData::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de, F, C, const D: usize> Deserialize<'de> for PixieProof<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn deserialize<De: Deserializer<'de>>(
+        deserializer: De
+    ) -> Result<Self, De::Error> {
+        let data = ProofData::deserialize(deserializer)?;
+        Self::try_from(data).map_err(De::Error::custom)
+    }
+}
+
+impl<F, C, const D: usize> PixieProof<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    /// Compact binary encoding of the proof, see [Self::from_bytes]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Proof encoded by [Self::to_bytes]. Only checks the shape of the
+    /// proof, whether it holds is up to `verify_proof`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use super::*;
+    use crate::{
+        fixtures,
+        stark_pixie_zkvm::{
+            generate_proof,
+            verify_proof,
+            Table,
+        },
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    /// A proof of the add program verifies the same after a round trip
+    /// through bytes and through JSON
+    fn test_round_trip_add_program() {
+        let program = fixtures::add();
+        let proof = generate_proof::<F, C, D>(&program).unwrap();
+        let public_values = verify_proof(&proof, &program).unwrap();
+
+        let bytes = proof
+            .to_bytes()
+            .unwrap();
+        let deserialized = PixieProof::<F, C, D>::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            deserialized
+                .to_bytes()
+                .unwrap(),
+            bytes
+        );
+        assert_eq!(
+            verify_proof(&deserialized, &program).unwrap(),
+            public_values
+        );
+
+        let json = serde_json::to_string(&proof).unwrap();
+        let deserialized: PixieProof<F, C, D> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            verify_proof(&deserialized, &program).unwrap(),
+            public_values
+        );
+    }
+
+    #[test]
+    /// Truncated bytes and proofs for the wrong number of tables do not
+    /// deserialize
+    fn test_malformed_bytes() {
+        let proof = generate_proof::<F, C, D>(&fixtures::add()).unwrap();
+        let bytes = proof
+            .to_bytes()
+            .unwrap();
+        assert!(PixieProof::<F, C, D>::from_bytes(&bytes[..bytes.len() / 2])
+            .is_err());
+
+        let mut data = ProofData::from(proof);
+        data.stark_proofs
+            .truncate(Table::Memory as usize);
+        let bytes = bincode::serialize(&data).unwrap();
+        assert!(PixieProof::<F, C, D>::from_bytes(&bytes).is_err());
+    }
+}
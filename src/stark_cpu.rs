@@ -3,6 +3,10 @@
 //! to be linked to the static code "Program" by having a cross-table
 //! -lookup with `ProgramInstructionsStark`.
 
+use anyhow::{
+    anyhow,
+    Result,
+};
 use core::marker::PhantomData;
 use plonky2::{
     field::{
@@ -12,6 +16,7 @@ use plonky2::{
         },
         packed::PackedField,
         polynomial::PolynomialValues,
+        types::Field,
     },
     hash::hash_types::RichField,
     iop::ext_target::ExtensionTarget,
@@ -22,38 +27,358 @@ use starky::{
         ConstraintConsumer,
         RecursiveConstraintConsumer,
     },
-    evaluation_frame::StarkFrame,
+    evaluation_frame::{
+        StarkEvaluationFrame,
+        StarkFrame,
+    },
+    lookup::{
+        Column,
+        Filter,
+    },
     stark::Stark,
     util::trace_rows_to_poly_values,
 };
 
 use crate::{
-    preflight_simulator::PreflightSimulation,
+    preflight_simulator::{
+        PreflightSimulation,
+        SimulationRow,
+    },
+    stark_rangecheck_u8::{
+        byte_weights,
+        word_bytes,
+    },
     utilities::debug_table,
-    vm_specs::Instruction,
+    vm_specs::{
+        Instruction,
+        INSTRUCTION_DATA_WEIGHTS,
+        OPCODE_COUNT,
+        REGISTER_COUNT,
+        REGISTER_NAMES,
+        Word,
+        WORD_BYTES,
+    },
 };
 
 // Table description:
-// +-----+----+--------+--------+--------------+---------+-------------+
-// | Clk | PC | Reg R0 | Reg R1 | Location     | Opcode* | Is_Executed |
-// +-----+----+--------+--------+--------------+---------+-------------+
-// | ..  | .. | ...    | ...    |  ....        |  ...    |             |
-// +-----+----+--------+--------+--------------+---------+-------------+
+// +-----+----+--------+-----+--------+----------+---------+-----------+-----------+---------+---------+-------------+----------+----------+---------+--------+
+// | Clk | PC | Reg R0 | ... | Reg Rn | Location | Mem_Val | Operand A | Operand B | Opcode* | Is_Init | Is_Executed | Mov_Dst* | Mov_Src* | Mov_Val | Bytes* |
+// +-----+----+--------+-----+--------+----------+---------+-----------+-----------+---------+---------+-------------+----------+----------+---------+--------+
+// | ..  | .. | ...    | ... | ...    |  ....    |  ...    |   ...     |   ...     |  ...    |         |             |   ...    |   ...    |   ...   |  ...   |
+// +-----+----+--------+-----+--------+----------+---------+-----------+-----------+---------+---------+-------------+----------+----------+---------+--------+
 //
 // `Opcode*` means `Opcode` that is one-hot encoded
+// `Reg R0` to `Reg Rn` are one column per register, see `REGISTER_COUNT`.
 // `Location` can be either Memory or Instruction location.
-// 5 Columns for `Clk`, `PC`, `Reg R0`, `Reg R1`, `Location`
-// 11 Columns for opcodes. See `Instruction::get_opcode`.
+// `Mem_Val` is the value loaded by `Lb` or stored by `Sb`, `0` otherwise.
+// `Operand A` and `Operand B` are the operands of the instruction, see
+// `Instruction::get_operands`. Immediates, such as those of `Li`, `Lis`
+// and the shift amounts of `Shl` and `Shr`, are carried in `Operand B`.
+// `6 + REGISTER_COUNT` Columns for `Clk`, `PC`, the registers, `Location`,
+// `Mem_Val`, `Operand A`, `Operand B`
+// 23 Columns for opcodes. See `Instruction::get_opcode`.
+// 1 Column for `Is_Init`
 // 1 Column for `Is_Executed`
-const NUM_DYNAMIC_COLS: usize = 5;
-const NUM_OPCODE_ONEHOT: usize = 11;
-const NUMBER_OF_COLS: usize = NUM_DYNAMIC_COLS + NUM_OPCODE_ONEHOT + 1;
-const ROW_HEADINGS: [&str; NUMBER_OF_COLS] = [
-    "clk", "pc", "r0", "r1", "loc", "op_add", "op_sub", "op_mul", "op_div",
-    "op_shl", "op_shr", "op_jz", "op_jnz", "op_lb", "op_sb", "op_halt",
-    "is_exec",
-];
-const PUBLIC_INPUTS: usize = 0;
+// `2 * REGISTER_COUNT + 1` Columns for `Mov_Dst*`, `Mov_Src*` and
+// `Mov_Val`: on `Mov` rows, one-hot selectors of the destination and the
+// source register, and the value of the source register. All `0` on any
+// other row.
+// `WORD_BYTES * (REGISTER_COUNT + 1)` Columns for `Bytes*`: the little
+// endian bytes of `Reg R0` to `Reg Rn` and of `Mem_Val`, in that order.
+// Each of them is looked up in the `RangeCheckU8Stark` table on executed
+// rows, which proves the registers and `Mem_Val` to be words.
+//
+// The table may start with an optional init row at `clk = 0`, like the
+// init rows of the memory table. It holds the machine state before the
+// first instruction executes: all registers `0` and `PC` at the entry
+// point. It is not executed, so no opcode is hot on it and it takes part
+// in no lookup.
+//
+// Public inputs are the machine state `(Clk, PC, Reg R0, ..., Reg Rn)` of
+// the first and of the last executed row, so that tables proving
+// consecutive parts of an execution can be chained together. They are
+// followed by `Ends_In_Halt`, set if the last executed row is a `Halt`.
+// A `Halt` is always the last executed row, so `Ends_In_Halt` tells a
+// whole execution apart from one stopped early.
+const COL_CLK: usize = 0;
+const COL_PC: usize = 1;
+const COL_REGISTERS: usize = 2;
+const COL_LOC: usize = COL_REGISTERS + REGISTER_COUNT;
+const COL_MEM_VAL: usize = COL_LOC + 1;
+const COL_OPERAND_A: usize = COL_LOC + 2;
+const COL_OPERAND_B: usize = COL_LOC + 3;
+const NUM_DYNAMIC_COLS: usize = COL_OPERAND_B + 1;
+const NUM_OPCODE_ONEHOT: usize = OPCODE_COUNT;
+const COL_OPCODES: usize = NUM_DYNAMIC_COLS;
+const COL_IS_INIT: usize = COL_OPCODES + NUM_OPCODE_ONEHOT;
+const COL_IS_EXEC: usize = COL_IS_INIT + 1;
+const COL_MOV_DST: usize = COL_IS_EXEC + 1;
+const COL_MOV_SRC: usize = COL_MOV_DST + REGISTER_COUNT;
+const COL_MOV_VAL: usize = COL_MOV_SRC + REGISTER_COUNT;
+const COL_BYTES: usize = COL_MOV_VAL + 1;
+const NUM_BYTE_COLS: usize = WORD_BYTES * (REGISTER_COUNT + 1);
+const NUMBER_OF_COLS: usize = COL_BYTES + NUM_BYTE_COLS;
+
+/// Columns holding a [Word] which is decomposed into `Bytes*`, the bytes
+/// of the `idx`-th starting at `COL_BYTES + idx * WORD_BYTES`
+const WORD_COLS: [usize; REGISTER_COUNT + 1] = {
+    let mut cols = [COL_MEM_VAL; REGISTER_COUNT + 1];
+    let mut idx = 0;
+    while idx < REGISTER_COUNT {
+        cols[idx] = COL_REGISTERS + idx;
+        idx += 1;
+    }
+    cols
+};
+
+/// Index, in the order `eval_packed_generic` yields them, of the
+/// constraint recomposing the `idx`-th of [WORD_COLS] from its `Bytes*`.
+/// It follows the 3 constraints on `Is_Executed` and `Is_Init`, one per
+/// opcode bit, their sum and the recompositions of the words before it.
+pub const fn word_recomposition_constraint(idx: usize) -> usize {
+    3 + NUM_OPCODE_ONEHOT + 1 + idx
+}
+
+/// Lays out per-column metadata: `clk`, `pc`, one entry per register,
+/// one entry per column following the registers up to `Is_Executed`, one
+/// for all of the `Mov_Dst*`, one for all of the `Mov_Src*`, one for
+/// `Mov_Val` and one for all of the `Bytes*`
+const fn column_layout(
+    clk: &'static str,
+    pc: &'static str,
+    registers: [&'static str; REGISTER_COUNT],
+    rest: [&'static str; COL_MOV_DST - COL_LOC],
+    [mov_dst, mov_src, mov_val]: [&'static str; 3],
+    bytes: &'static str,
+) -> [&'static str; NUMBER_OF_COLS] {
+    let mut layout = [""; NUMBER_OF_COLS];
+    layout[COL_CLK] = clk;
+    layout[COL_PC] = pc;
+    let mut idx = 0;
+    while idx < REGISTER_COUNT {
+        layout[COL_REGISTERS + idx] = registers[idx];
+        idx += 1;
+    }
+    let mut idx = 0;
+    while idx < rest.len() {
+        layout[COL_LOC + idx] = rest[idx];
+        idx += 1;
+    }
+    let mut idx = 0;
+    while idx < REGISTER_COUNT {
+        layout[COL_MOV_DST + idx] = mov_dst;
+        layout[COL_MOV_SRC + idx] = mov_src;
+        idx += 1;
+    }
+    layout[COL_MOV_VAL] = mov_val;
+    let mut idx = COL_BYTES;
+    while idx < NUMBER_OF_COLS {
+        layout[idx] = bytes;
+        idx += 1;
+    }
+    layout
+}
+
+const ROW_HEADINGS: [&str; NUMBER_OF_COLS] = column_layout(
+    "clk",
+    "pc",
+    REGISTER_NAMES,
+    [
+        "loc",
+        "mem_val",
+        "operand_a",
+        "operand_b",
+        "op_add",
+        "op_sub",
+        "op_mul",
+        "op_div",
+        "op_shl",
+        "op_shr",
+        "op_jz",
+        "op_jnz",
+        "op_lb",
+        "op_sb",
+        "op_halt",
+        "op_and",
+        "op_or",
+        "op_xor",
+        "op_not",
+        "op_li",
+        "op_lis",
+        "op_mov",
+        "op_slt",
+        "op_seq",
+        "op_out",
+        "op_call",
+        "op_ret",
+        "is_init",
+        "is_exec",
+    ],
+    ["mov_dst", "mov_src", "mov_val"],
+    "byte",
+);
+const COLUMN_DESCRIPTIONS: [&str; NUMBER_OF_COLS] = column_layout(
+    "Clock cycle of the row",
+    "Program counter of the executed instruction",
+    ["Register before the instruction executes"; REGISTER_COUNT],
+    [
+        "Memory or instruction location operand, `0` if none",
+        "Value loaded by `Lb` or stored by `Sb`, `0` otherwise",
+        "First operand of the instruction",
+        "Second operand of the instruction",
+        "Set if executing `Add`",
+        "Set if executing `Sub`",
+        "Set if executing `Mul`",
+        "Set if executing `Div`",
+        "Set if executing `Shl`",
+        "Set if executing `Shr`",
+        "Set if executing `Jz`",
+        "Set if executing `Jnz`",
+        "Set if executing `Lb`",
+        "Set if executing `Sb`",
+        "Set if executing `Halt`",
+        "Set if executing `And`",
+        "Set if executing `Or`",
+        "Set if executing `Xor`",
+        "Set if executing `Not`",
+        "Set if executing `Li`",
+        "Set if executing `Lis`",
+        "Set if executing `Mov`",
+        "Set if executing `Slt`",
+        "Set if executing `Seq`",
+        "Set if executing `Out`",
+        "Set if executing `Call`",
+        "Set if executing `Ret`",
+        "Set on the `clk = 0` init row only",
+        "Set on actual rows, unset on padding",
+    ],
+    [
+        "Set for the destination register of `Mov`",
+        "Set for the source register of `Mov`",
+        "Value of the source register of `Mov`, `0` otherwise",
+    ],
+    "Little endian byte of a register or of `Mem_Val`",
+);
+
+// `column_layout` leaves a column without heading if the table grows
+// without it, e.g. when `REGISTER_COUNT` changes
+const _: () = {
+    assert!(ROW_HEADINGS.len() == NUMBER_OF_COLS);
+    let mut idx = 0;
+    while idx < NUMBER_OF_COLS {
+        assert!(!ROW_HEADINGS[idx].is_empty());
+        assert!(!COLUMN_DESCRIPTIONS[idx].is_empty());
+        idx += 1;
+    }
+};
+
+// `Clk`, `PC` and the registers are adjacent, starting at `COL_CLK`
+pub const NUM_STATE_COLS: usize = COL_LOC;
+const PUBLIC_INPUTS: usize = 2 * NUM_STATE_COLS + 1;
+const PI_START: usize = 0;
+const PI_END: usize = NUM_STATE_COLS;
+const PI_ENDS_IN_HALT: usize = 2 * NUM_STATE_COLS;
+
+// Opcode column of `Halt`, see `Instruction::get_opcode`
+const COL_OP_HALT: usize = COL_OPCODES + 10;
+
+// Opcode column of `Mov`, see `Instruction::get_opcode`
+const COL_OP_MOV: usize = COL_OPCODES + 17;
+
+// Opcode columns of memory touching instructions, see `Instruction::get_opcode`
+const COL_OP_LB: usize = COL_OPCODES + 8;
+const COL_OP_SB: usize = COL_OPCODES + 9;
+
+// Opcode columns of instructions whose second operand is `Location`
+const LOCATION_OPERAND_COLS: [usize; 4] =
+    [COL_OPCODES + 6, COL_OPCODES + 7, COL_OP_LB, COL_OP_SB];
+
+/// Columns which, when summed, are `1` only for rows executing an `Lb` or
+/// an `Sb`. All other rows (arithmetic, moves, jumps, `Halt` and padding)
+/// sum to `0` and hence never take part in the CPU <-> Memory lookup.
+pub const MEMORY_OP_COLS: [usize; 2] = [COL_OP_LB, COL_OP_SB];
+
+/// Opcodes, see `Instruction::get_opcode`, whose effect on the machine
+/// state (registers, `PC` and memory) is fully constrained by this table.
+/// Rows executing any other opcode are only tied to the program and to
+/// the memory table, so their results are not proven correct. Extend this
+/// whenever an opcode gets its semantics constrained.
+///
+/// `Halt` changes no state and is constrained to be the last executed row.
+/// `Mov` copies its source register into its destination register and
+/// moves on to the next instruction, leaving everything else as it was.
+pub const CONSTRAINED_OPCODES: [u8; 2] = [10, 17];
+
+/// Filter for the CPU side of the CPU <-> Memory cross-table lookup
+pub fn ctl_filter_memory<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::sum(MEMORY_OP_COLS))
+}
+
+/// `(Clk, Location, Mem_Val)` looked up in the memory table as
+/// `(Clock, MemoryAddress, Value)`
+pub fn ctl_data_memory<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_CLK, COL_LOC, COL_MEM_VAL]).collect()
+}
+
+/// Filter for the CPU side of the CPU <-> Program cross-table lookup
+pub fn ctl_filter_program<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::single(COL_IS_EXEC))
+}
+
+/// `instruction_data` of the executed instruction, see
+/// `Instruction::instruction_data`, looked up in the program table. The
+/// opcode is recovered from its one-hot encoding.
+pub fn ctl_data_program<F: Field>() -> Vec<Column<F>> {
+    let [pc_weight, opcode_weight, operand_a_weight, operand_b_weight] =
+        INSTRUCTION_DATA_WEIGHTS.map(F::from_canonical_u64);
+    let opcodes = (0..NUM_OPCODE_ONEHOT).map(|opcode| {
+        (
+            COL_OPCODES + opcode,
+            F::from_canonical_usize(opcode) * opcode_weight,
+        )
+    });
+    vec![Column::linear_combination(
+        [
+            (COL_PC, pc_weight),
+            (COL_OPERAND_A, operand_a_weight),
+            (COL_OPERAND_B, operand_b_weight),
+        ]
+        .into_iter()
+        .chain(opcodes),
+    )]
+}
+
+/// `PC` of the executed instruction, looked up in the program table on
+/// the rows of [ctl_filter_program]
+pub fn ctl_data_program_counter<F: Field>() -> Vec<Column<F>> {
+    vec![Column::single(COL_PC)]
+}
+
+/// Filter for the CPU side of the range check lookups
+pub fn ctl_filter_range_check<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::single(COL_IS_EXEC))
+}
+
+/// Each of the `Bytes*`, looked up on its own in the byte table
+pub fn ctl_data_range_check<F: Field>() -> Vec<Vec<Column<F>>> {
+    (COL_BYTES..NUMBER_OF_COLS)
+        .map(|col| vec![Column::single(col)])
+        .collect()
+}
+
+/// Values of the `Bytes*` on the executed rows of `trace`, i.e. all the
+/// values looked up in the byte table
+pub fn range_checked_values<F: Field>(
+    trace: &[PolynomialValues<F>]
+) -> Vec<F> {
+    let is_exec = &trace[COL_IS_EXEC].values;
+    (0..is_exec.len())
+        .filter(|&row| is_exec[row] == F::ONE)
+        .flat_map(|row| {
+            (COL_BYTES..NUMBER_OF_COLS).map(move |col| trace[col].values[row])
+        })
+        .collect()
+}
 
 #[derive(Clone, Copy)]
 pub struct CPUStark<F, const D: usize> {
@@ -68,30 +393,164 @@ where
         Self { _f: PhantomData }
     }
 
+    /// Heading and a short description of every column of the table
+    pub fn column_docs() -> Vec<(&'static str, &'static str)> {
+        ROW_HEADINGS
+            .into_iter()
+            .zip(COLUMN_DESCRIPTIONS)
+            .collect()
+    }
+
     pub fn generate_trace(sim: &PreflightSimulation) -> Vec<PolynomialValues<F>>
     where
         F: RichField,
     {
-        let mut trace = sim
+        Self::generate_trace_from_rows(&sim.trace_rows)
+    }
+
+    /// Like [Self::generate_trace], but preceded by the `clk = 0` init row
+    /// holding the machine state before execution
+    pub fn generate_trace_with_init_row(
+        sim: &PreflightSimulation
+    ) -> Vec<PolynomialValues<F>>
+    where
+        F: RichField,
+    {
+        let mut init_row = [F::ZERO; NUMBER_OF_COLS];
+        if let Some(first) = sim
             .trace_rows
+            .first()
+        {
+            init_row[COL_PC] = F::from_canonical_u8(first.program_counter);
+        }
+        init_row[COL_IS_INIT] = F::ONE;
+
+        let mut trace = vec![init_row];
+        trace.extend(Self::executed_rows(&sim.trace_rows));
+        Self::pad_trace(trace)
+    }
+
+    /// Machine state of the first and last of `rows`, and whether the
+    /// last of them executes a `Halt`, laid out as the public inputs of
+    /// this table. All zeroes if there are no rows.
+    pub fn public_inputs(rows: &[SimulationRow]) -> [F; PUBLIC_INPUTS] {
+        let mut public_inputs = [F::ZERO; PUBLIC_INPUTS];
+        if let (Some(first), Some(last)) = (rows.first(), rows.last()) {
+            public_inputs[PI_START..PI_START + NUM_STATE_COLS]
+                .copy_from_slice(&Self::state(first));
+            public_inputs[PI_END..PI_END + NUM_STATE_COLS]
+                .copy_from_slice(&Self::state(last));
+            public_inputs[PI_ENDS_IN_HALT] =
+                F::from_bool(last.instruction == Instruction::Halt);
+        }
+        public_inputs
+    }
+
+    /// Machine states of the first and of the last executed row, as found
+    /// in `public_inputs`, see [Self::public_inputs]
+    pub fn boundaries(public_inputs: &[F]) -> (&[F], &[F]) {
+        (
+            &public_inputs[PI_START..PI_START + NUM_STATE_COLS],
+            &public_inputs[PI_END..PI_END + NUM_STATE_COLS],
+        )
+    }
+
+    /// Whether the last executed row is a `Halt`, as found in
+    /// `public_inputs`, see [Self::public_inputs]
+    pub fn ends_in_halt(public_inputs: &[F]) -> Result<bool> {
+        if public_inputs.len() != PUBLIC_INPUTS {
+            return Err(anyhow!("malformed CPU public inputs"));
+        }
+        match public_inputs[PI_ENDS_IN_HALT] {
+            flag if flag == F::ZERO => Ok(false),
+            flag if flag == F::ONE => Ok(true),
+            flag => Err(anyhow!("`Ends_In_Halt` of {} is not a bit", flag)),
+        }
+    }
+
+    /// Registers of the last executed row, as found in `public_inputs`,
+    /// see [Self::public_inputs]
+    pub fn final_registers(
+        public_inputs: &[F]
+    ) -> Result<[Word; REGISTER_COUNT]> {
+        if public_inputs.len() != PUBLIC_INPUTS {
+            return Err(anyhow!("malformed CPU public inputs"));
+        }
+        let registers = &public_inputs
+            [PI_END + COL_REGISTERS..PI_END + COL_REGISTERS + REGISTER_COUNT];
+        let mut words = [0; REGISTER_COUNT];
+        for (word, &register) in words
+            .iter_mut()
+            .zip(registers)
+        {
+            *word = Word::try_from(register.to_canonical_u64())
+                .map_err(|_| anyhow!("register {} is not a word", register))?;
+        }
+        Ok(words)
+    }
+
+    /// `(Clk, PC, Reg R0, ..., Reg Rn)` of `row`
+    pub fn state(row: &SimulationRow) -> [F; NUM_STATE_COLS] {
+        let mut state = [F::ZERO; NUM_STATE_COLS];
+        state[COL_CLK] = F::from_canonical_u32(row.clock);
+        state[COL_PC] = F::from_canonical_u8(row.program_counter);
+        for (idx, &register) in row
+            .registers
             .iter()
+            .enumerate()
+        {
+            state[COL_REGISTERS + idx] = F::from_canonical_u32(register);
+        }
+        state
+    }
+
+    /// Trace of a contiguous run of `rows`, not necessarily starting at
+    /// the beginning of the execution
+    pub fn generate_trace_from_rows(
+        rows: &[SimulationRow]
+    ) -> Vec<PolynomialValues<F>>
+    where
+        F: RichField,
+    {
+        Self::pad_trace(Self::executed_rows(rows))
+    }
+
+    /// One table row per executed row in `rows`
+    fn executed_rows(rows: &[SimulationRow]) -> Vec<[F; NUMBER_OF_COLS]>
+    where
+        F: RichField,
+    {
+        rows.iter()
             .map(|row| {
+                let [operand_a, operand_b] = row
+                    .instruction
+                    .get_operands();
+                // Memory Value (if any accessed). `memory_snapshot` is the
+                // state before this row executes.
+                let mem_val = match row.instruction {
+                    Instruction::Lb(_, l) => row
+                        .get_memory_at(&l.0)
+                        .unwrap_or_default(),
+                    Instruction::Sb(r, _) => row.registers[usize::from(r)],
+                    _ => 0,
+                };
+                // Clock, Program Counter and Registers
+                let state = Self::state(row);
                 let dynamic_elems = [
-                    // Clock
-                    F::from_canonical_u32(row.clock),
-                    // Program Counter
-                    F::from_canonical_u8(row.program_counter),
-                    // Registers
-                    F::from_canonical_u8(row.registers[0]),
-                    F::from_canonical_u8(row.registers[1]),
                     // Memory Address (if any accessed)
-                    F::from_canonical_u8(match row.instruction {
-                        Instruction::Jz(_, l) => l.0,
-                        Instruction::Jnz(_, l) => l.0,
+                    F::from_canonical_u32(match row.instruction {
+                        Instruction::Jz(_, l) => Word::from(l.0),
+                        Instruction::Jnz(_, l) => Word::from(l.0),
+                        Instruction::Call(l) => Word::from(l.0),
                         Instruction::Lb(_, l) => l.0,
                         Instruction::Sb(_, l) => l.0,
                         _ => 0,
                     }),
+                    // Memory Value
+                    F::from_canonical_u32(mem_val),
+                    // Operands of the instruction
+                    F::from_canonical_u32(operand_a),
+                    F::from_canonical_u32(operand_b),
                 ];
                 let opcode_one_hot = row
                     .instruction
@@ -99,7 +558,10 @@ where
 
                 let mut table_row = [F::ZERO; NUMBER_OF_COLS];
                 let mut idx = 0;
-                for elem in dynamic_elems {
+                for elem in state
+                    .into_iter()
+                    .chain(dynamic_elems)
+                {
                     table_row[idx] = elem;
                     idx += 1;
                 }
@@ -108,12 +570,39 @@ where
                     idx += 1;
                 }
                 // `Is_Executed`
-                table_row[NUMBER_OF_COLS - 1] = F::ONE;
+                table_row[COL_IS_EXEC] = F::ONE;
+                // `Mov_Dst*`, `Mov_Src*` and `Mov_Val`
+                if let Instruction::Mov(dst, src) = row.instruction {
+                    let (dst, src) = (usize::from(dst), usize::from(src));
+                    table_row[COL_MOV_DST + dst] = F::ONE;
+                    table_row[COL_MOV_SRC + src] = F::ONE;
+                    table_row[COL_MOV_VAL] =
+                        F::from_canonical_u32(row.registers[src]);
+                }
+                // `Bytes*`, in the order of `WORD_COLS`
+                for (idx, word) in row
+                    .registers
+                    .into_iter()
+                    .chain([mem_val])
+                    .enumerate()
+                {
+                    let start = COL_BYTES + idx * WORD_BYTES;
+                    table_row[start..start + WORD_BYTES]
+                        .copy_from_slice(&word_bytes(word));
+                }
 
                 table_row
             })
-            .collect::<Vec<[F; NUMBER_OF_COLS]>>();
+            .collect()
+    }
 
+    /// Pads `trace` to a power of two length and converts it into columns
+    fn pad_trace(
+        mut trace: Vec<[F; NUMBER_OF_COLS]>
+    ) -> Vec<PolynomialValues<F>>
+    where
+        F: RichField,
+    {
         debug_table("CPU", ROW_HEADINGS, &trace);
 
         // Need to pad the trace to a len of some power of 2
@@ -153,73 +642,856 @@ where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let local_values = vars.get_local_values();
+        let next_values = vars.get_next_values();
+
+        // `Is_Executed` and `Is_Init` are boolean, and never both set
+        let is_exec = local_values[COL_IS_EXEC];
+        yield_constr.constraint(is_exec * (P::ONES - is_exec));
+        let is_init = local_values[COL_IS_INIT];
+        yield_constr.constraint(is_init * (P::ONES - is_init));
+        yield_constr.constraint(is_init * is_exec);
+
+        // Each opcode column is boolean and exactly one of them is set on
+        // executed rows. Padding rows have none set.
+        let opcodes =
+            &local_values[COL_OPCODES..COL_OPCODES + NUM_OPCODE_ONEHOT];
+        for &opcode in opcodes {
+            yield_constr.constraint(opcode * (P::ONES - opcode));
+        }
+        let opcode_sum = opcodes
+            .iter()
+            .fold(P::ZEROS, |acc, &opcode| acc + opcode);
+        yield_constr.constraint(opcode_sum - is_exec);
+
+        // Each of the registers and `Mem_Val` is made up of its `Bytes*`,
+        // which are looked up in the byte table. Keep the index of these
+        // in sync with [word_recomposition_constraint].
+        let weights = byte_weights::<FE>();
+        for (idx, col) in WORD_COLS
+            .into_iter()
+            .enumerate()
+        {
+            let start = COL_BYTES + idx * WORD_BYTES;
+            let recomposed = local_values[start..start + WORD_BYTES]
+                .iter()
+                .zip(weights)
+                .fold(P::ZEROS, |acc, (&byte, weight)| acc + byte * weight);
+            yield_constr.constraint(local_values[col] - recomposed);
+        }
+
+        // `Location` is the second operand of the instruction for jumps,
+        // loads and stores. Operands are tied to the program through the
+        // CPU <-> Program lookup, this ties the memory accesses to them.
+        let has_location_operand = LOCATION_OPERAND_COLS
+            .iter()
+            .fold(P::ZEROS, |acc, &col| acc + local_values[col]);
+        yield_constr.constraint(
+            has_location_operand
+                * (local_values[COL_LOC] - local_values[COL_OPERAND_B]),
+        );
+
+        // `Is_Executed` never goes from `0` back to `1`, i.e. once padding
+        // starts, it continues till the end of the table. The init row is
+        // the only unexecuted row which may precede executed ones.
+        let next_is_exec = next_values[COL_IS_EXEC];
+        yield_constr.constraint_transition(
+            next_is_exec * (P::ONES - is_exec - is_init),
+        );
+
+        // `Mov_Dst*` and `Mov_Src*` are boolean, one of each is set on
+        // `Mov` rows and none on any other row. They select the registers
+        // given by the operands, which are tied to the program.
+        let op_mov = local_values[COL_OP_MOV];
+        let mov_dst = &local_values[COL_MOV_DST..COL_MOV_DST + REGISTER_COUNT];
+        let mov_src = &local_values[COL_MOV_SRC..COL_MOV_SRC + REGISTER_COUNT];
+        for (selectors, operand) in
+            [(mov_dst, COL_OPERAND_A), (mov_src, COL_OPERAND_B)]
+        {
+            for &selector in selectors {
+                yield_constr.constraint(selector * (P::ONES - selector));
+            }
+            let (count, index) = selectors
+                .iter()
+                .enumerate()
+                .fold((P::ZEROS, P::ZEROS), |(count, index), (idx, &sel)| {
+                    (count + sel, index + sel * FE::from_canonical_usize(idx))
+                });
+            yield_constr.constraint(count - op_mov);
+            yield_constr.constraint(op_mov * local_values[operand] - index);
+        }
+
+        // `Mov_Val` is the source register, which the next row holds in
+        // the destination register. All other registers are unchanged
+        // and `PC` moves on to the next instruction.
+        let mov_val = local_values[COL_MOV_VAL];
+        let selected = mov_src
+            .iter()
+            .zip(&local_values[COL_REGISTERS..COL_REGISTERS + REGISTER_COUNT])
+            .fold(P::ZEROS, |acc, (&sel, &register)| acc + sel * register);
+        yield_constr.constraint(mov_val - selected);
+        for idx in 0..REGISTER_COUNT {
+            let next_register = next_values[COL_REGISTERS + idx];
+            yield_constr.constraint_transition(
+                next_is_exec * mov_dst[idx] * (next_register - mov_val),
+            );
+            yield_constr.constraint_transition(
+                next_is_exec
+                    * (op_mov - mov_dst[idx])
+                    * (next_register - local_values[COL_REGISTERS + idx]),
+            );
+        }
+        yield_constr.constraint_transition(
+            next_is_exec
+                * op_mov
+                * (next_values[COL_PC] - local_values[COL_PC] - P::ONES),
+        );
+
+        // Nothing executes after a `Halt`
+        let op_halt = local_values[COL_OP_HALT];
+        yield_constr.constraint_transition(op_halt * next_is_exec);
+
+        // The init row can only be the first row, at `clk = 0` with all
+        // registers `0`. The first executed row starts from its state.
+        yield_constr.constraint_transition(next_values[COL_IS_INIT]);
+        yield_constr.constraint(is_init * local_values[COL_CLK]);
+        for idx in 0..REGISTER_COUNT {
+            let register = local_values[COL_REGISTERS + idx];
+            yield_constr.constraint(is_init * register);
+            yield_constr.constraint_transition(
+                is_init
+                    * next_is_exec
+                    * (next_values[COL_REGISTERS + idx] - register),
+            );
+        }
+        let pc_diff = next_values[COL_PC] - local_values[COL_PC];
+        yield_constr.constraint_transition(is_init * next_is_exec * pc_diff);
+
+        // Clock increases by one between consecutive executed rows
+        let clk = local_values[COL_CLK];
+        let next_clk = next_values[COL_CLK];
+        yield_constr
+            .constraint_transition(next_is_exec * (next_clk - clk - P::ONES));
+
+        // The first and the last executed rows carry the state given in
+        // the public inputs. The first executed row is either the first
+        // row of the table or follows the init row. The last executed row
+        // is either followed by padding or is the last row of the table.
+        let public_inputs = vars.get_public_inputs();
+        for idx in 0..NUM_STATE_COLS {
+            let value = local_values[COL_CLK + idx];
+            yield_constr.constraint_first_row(
+                is_exec * (value - public_inputs[PI_START + idx]),
+            );
+            yield_constr.constraint_transition(
+                is_init
+                    * next_is_exec
+                    * (next_values[COL_CLK + idx]
+                        - public_inputs[PI_START + idx]),
+            );
+            let end_diff = value - public_inputs[PI_END + idx];
+            yield_constr.constraint_transition(
+                is_exec * (P::ONES - next_is_exec) * end_diff,
+            );
+            yield_constr.constraint_last_row(is_exec * end_diff);
+        }
+
+        // The last executed row is a `Halt` exactly if the public inputs
+        // say so. With `Is_Executed` never going back to `1`, this leaves
+        // no way to stop early and still claim a whole execution.
+        let halt_diff = op_halt - public_inputs[PI_ENDS_IN_HALT];
+        yield_constr.constraint_transition(
+            is_exec * (P::ONES - next_is_exec) * halt_diff,
+        );
+        yield_constr.constraint_last_row(is_exec * halt_diff);
     }
 
     fn eval_ext_circuit(
         &self,
-        _builder: &mut CircuitBuilder<F, D>,
-        _vars: &Self::EvaluationFrameTarget,
-        _yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: &Self::EvaluationFrameTarget,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
-        unimplemented!()
+        let local_values = vars.get_local_values();
+        let next_values = vars.get_next_values();
+        let one = builder.one_extension();
+
+        // `Is_Executed` and `Is_Init` are boolean, and never both set
+        let is_exec = local_values[COL_IS_EXEC];
+        let not_exec = builder.sub_extension(one, is_exec);
+        let constraint = builder.mul_extension(is_exec, not_exec);
+        yield_constr.constraint(builder, constraint);
+        let is_init = local_values[COL_IS_INIT];
+        let not_init = builder.sub_extension(one, is_init);
+        let constraint = builder.mul_extension(is_init, not_init);
+        yield_constr.constraint(builder, constraint);
+        let constraint = builder.mul_extension(is_init, is_exec);
+        yield_constr.constraint(builder, constraint);
+
+        // Each opcode column is boolean and exactly one of them is set on
+        // executed rows
+        let opcodes =
+            &local_values[COL_OPCODES..COL_OPCODES + NUM_OPCODE_ONEHOT];
+        for &opcode in opcodes {
+            let not_opcode = builder.sub_extension(one, opcode);
+            let constraint = builder.mul_extension(opcode, not_opcode);
+            yield_constr.constraint(builder, constraint);
+        }
+        let opcode_sum = builder.add_many_extension(opcodes);
+        let constraint = builder.sub_extension(opcode_sum, is_exec);
+        yield_constr.constraint(builder, constraint);
+
+        // Each of the registers and `Mem_Val` is made up of its `Bytes*`
+        let weights = byte_weights::<F>();
+        for (idx, col) in WORD_COLS
+            .into_iter()
+            .enumerate()
+        {
+            let start = COL_BYTES + idx * WORD_BYTES;
+            let mut recomposed = builder.zero_extension();
+            for (byte_idx, weight) in weights
+                .into_iter()
+                .enumerate()
+            {
+                recomposed = builder.mul_const_add_extension(
+                    weight,
+                    local_values[start + byte_idx],
+                    recomposed,
+                );
+            }
+            let constraint =
+                builder.sub_extension(local_values[col], recomposed);
+            yield_constr.constraint(builder, constraint);
+        }
+
+        // `Location` is the second operand of jumps, loads and stores
+        let has_location_operand = builder.add_many_extension(
+            LOCATION_OPERAND_COLS.map(|col| local_values[col]),
+        );
+        let loc_diff = builder
+            .sub_extension(local_values[COL_LOC], local_values[COL_OPERAND_B]);
+        let constraint = builder.mul_extension(has_location_operand, loc_diff);
+        yield_constr.constraint(builder, constraint);
+
+        // `Is_Executed` never goes from `0` back to `1`, except after the
+        // init row
+        let next_is_exec = next_values[COL_IS_EXEC];
+        let not_exec_nor_init = builder.sub_extension(not_exec, is_init);
+        let constraint = builder.mul_extension(next_is_exec, not_exec_nor_init);
+        yield_constr.constraint_transition(builder, constraint);
+
+        // `Mov_Dst*` and `Mov_Src*` are one-hot on `Mov` rows, unset
+        // elsewhere, and select the registers given by the operands
+        let op_mov = local_values[COL_OP_MOV];
+        let mov_dst = &local_values[COL_MOV_DST..COL_MOV_DST + REGISTER_COUNT];
+        let mov_src = &local_values[COL_MOV_SRC..COL_MOV_SRC + REGISTER_COUNT];
+        for (selectors, operand) in
+            [(mov_dst, COL_OPERAND_A), (mov_src, COL_OPERAND_B)]
+        {
+            let mut index = builder.zero_extension();
+            for (idx, &selector) in selectors
+                .iter()
+                .enumerate()
+            {
+                let not_selector = builder.sub_extension(one, selector);
+                let constraint = builder.mul_extension(selector, not_selector);
+                yield_constr.constraint(builder, constraint);
+                index = builder.mul_const_add_extension(
+                    F::from_canonical_usize(idx),
+                    selector,
+                    index,
+                );
+            }
+            let count = builder.add_many_extension(selectors);
+            let constraint = builder.sub_extension(count, op_mov);
+            yield_constr.constraint(builder, constraint);
+            let constraint = builder.mul_sub_extension(
+                op_mov,
+                local_values[operand],
+                index,
+            );
+            yield_constr.constraint(builder, constraint);
+        }
+
+        // `Mov_Val` is the source register, moved into the destination
+        // register. All other registers are unchanged and `PC` moves on.
+        let mov_val = local_values[COL_MOV_VAL];
+        let mut selected = builder.zero_extension();
+        for idx in 0..REGISTER_COUNT {
+            selected = builder.mul_add_extension(
+                mov_src[idx],
+                local_values[COL_REGISTERS + idx],
+                selected,
+            );
+        }
+        let constraint = builder.sub_extension(mov_val, selected);
+        yield_constr.constraint(builder, constraint);
+        for idx in 0..REGISTER_COUNT {
+            let next_register = next_values[COL_REGISTERS + idx];
+            let moved_diff = builder.sub_extension(next_register, mov_val);
+            let constraint = builder.mul_many_extension([
+                next_is_exec,
+                mov_dst[idx],
+                moved_diff,
+            ]);
+            yield_constr.constraint_transition(builder, constraint);
+            let unselected = builder.sub_extension(op_mov, mov_dst[idx]);
+            let kept_diff = builder.sub_extension(
+                next_register,
+                local_values[COL_REGISTERS + idx],
+            );
+            let constraint = builder.mul_many_extension([
+                next_is_exec,
+                unselected,
+                kept_diff,
+            ]);
+            yield_constr.constraint_transition(builder, constraint);
+        }
+        let pc_step =
+            builder.sub_extension(next_values[COL_PC], local_values[COL_PC]);
+        let pc_step = builder.sub_extension(pc_step, one);
+        let constraint =
+            builder.mul_many_extension([next_is_exec, op_mov, pc_step]);
+        yield_constr.constraint_transition(builder, constraint);
+
+        // Nothing executes after a `Halt`
+        let op_halt = local_values[COL_OP_HALT];
+        let constraint = builder.mul_extension(op_halt, next_is_exec);
+        yield_constr.constraint_transition(builder, constraint);
+
+        // The init row can only be the first row, at `clk = 0` with all
+        // registers `0`. The first executed row starts from its state.
+        yield_constr.constraint_transition(builder, next_values[COL_IS_INIT]);
+        let constraint = builder.mul_extension(is_init, local_values[COL_CLK]);
+        yield_constr.constraint(builder, constraint);
+        let is_init_then_exec = builder.mul_extension(is_init, next_is_exec);
+        for idx in 0..REGISTER_COUNT {
+            let register = local_values[COL_REGISTERS + idx];
+            let constraint = builder.mul_extension(is_init, register);
+            yield_constr.constraint(builder, constraint);
+            let register_diff = builder
+                .sub_extension(next_values[COL_REGISTERS + idx], register);
+            let constraint =
+                builder.mul_extension(is_init_then_exec, register_diff);
+            yield_constr.constraint_transition(builder, constraint);
+        }
+        let pc_diff =
+            builder.sub_extension(next_values[COL_PC], local_values[COL_PC]);
+        let constraint = builder.mul_extension(is_init_then_exec, pc_diff);
+        yield_constr.constraint_transition(builder, constraint);
+
+        // Clock increases by one between consecutive executed rows
+        let clk_diff =
+            builder.sub_extension(next_values[COL_CLK], local_values[COL_CLK]);
+        let clk_step = builder.sub_extension(clk_diff, one);
+        let constraint = builder.mul_extension(next_is_exec, clk_step);
+        yield_constr.constraint_transition(builder, constraint);
+
+        // The first and the last executed rows carry the state given in
+        // the public inputs
+        let public_inputs = vars.get_public_inputs();
+        let not_next_exec = builder.sub_extension(one, next_is_exec);
+        let is_last_exec = builder.mul_extension(is_exec, not_next_exec);
+        for idx in 0..NUM_STATE_COLS {
+            let value = local_values[COL_CLK + idx];
+            let start_diff =
+                builder.sub_extension(value, public_inputs[PI_START + idx]);
+            let constraint = builder.mul_extension(is_exec, start_diff);
+            yield_constr.constraint_first_row(builder, constraint);
+            let next_start_diff = builder.sub_extension(
+                next_values[COL_CLK + idx],
+                public_inputs[PI_START + idx],
+            );
+            let constraint =
+                builder.mul_extension(is_init_then_exec, next_start_diff);
+            yield_constr.constraint_transition(builder, constraint);
+            let end_diff =
+                builder.sub_extension(value, public_inputs[PI_END + idx]);
+            let constraint = builder.mul_extension(is_last_exec, end_diff);
+            yield_constr.constraint_transition(builder, constraint);
+            let constraint = builder.mul_extension(is_exec, end_diff);
+            yield_constr.constraint_last_row(builder, constraint);
+        }
+
+        // The last executed row is a `Halt` exactly if the public inputs
+        // say so
+        let halt_diff =
+            builder.sub_extension(op_halt, public_inputs[PI_ENDS_IN_HALT]);
+        let constraint = builder.mul_extension(is_last_exec, halt_diff);
+        yield_constr.constraint_transition(builder, constraint);
+        let constraint = builder.mul_extension(is_exec, halt_diff);
+        yield_constr.constraint_last_row(builder, constraint);
     }
 
     fn constraint_degree(&self) -> usize {
         3
     }
+
+    fn requires_ctls(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use plonky2::{
-        field::goldilocks_field::GoldilocksField,
-        plonk::config::{
-            GenericConfig,
-            PoseidonGoldilocksConfig,
-        },
-        util::timing::TimingTree,
-    };
-    use starky::{
-        config::StarkConfig,
-        proof::StarkProofWithPublicInputs,
-        prover::prove,
-        verifier::verify_stark_proof,
+    use plonky2::plonk::config::{
+        GenericConfig,
+        PoseidonGoldilocksConfig,
     };
 
-    use crate::vm_specs::Program;
+    use starky::stark_testing::test_stark_circuit_constraints;
+
+    use crate::{
+        fixtures,
+        stark_pixie_zkvm::{
+            generate_public_inputs_from_rows,
+            generate_traces_from_rows,
+            known_program_columns,
+            prove_all,
+            prove_and_verify,
+            prove_and_verify_traces,
+            simulate_and_trace,
+            verify_all,
+            PixieZKVM,
+            StarkSet,
+            Table,
+            NUM_TABLES,
+        },
+        utilities::check_constraints,
+        vm_specs::{
+            MemoryLocation,
+            Program,
+            ProgramBuilder,
+            Register,
+            INSTRUCTION_ENCODING_WEIGHTS,
+        },
+    };
 
     use super::*;
 
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
     #[test]
     fn test_nil_program() {
-        const D: usize = 2;
-        type C = PoseidonGoldilocksConfig;
-        type F = <C as GenericConfig<D>>::F;
-        type S = CPUStark<F, D>;
-        type PR = StarkProofWithPublicInputs<GoldilocksField, C, 2>;
-
-        let stark = S::new();
-        let mut config = StarkConfig::standard_fast_config();
-        // Need to do this since our table is small. Need atleast 1<<5
-        // sized table to not affect this
-        config
-            .fri_config
-            .cap_height = 1;
-        let program = Program::default();
-        let simulation = PreflightSimulation::simulate(&program);
-        assert!(simulation.is_ok());
-        let simulation = simulation.unwrap();
-        let trace = CPUStark::<F, D>::generate_trace(&simulation);
-        let proof: Result<PR, anyhow::Error> = prove(
-            stark.clone(),
-            &config,
-            trace,
-            &[],
-            &mut TimingTree::default(),
+        let (simulation, traces) =
+            simulate_and_trace::<F, D>(&Program::default());
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    fn test_add_program() {
+        let (simulation, traces) = simulate_and_trace::<F, D>(&fixtures::add());
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    /// Constraints can be checked row by row, pointing at the row and the
+    /// constraint a corrupted trace breaks
+    fn test_check_constraints() {
+        let program = fixtures::add();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let public_inputs =
+            CPUStark::<F, D>::public_inputs(&simulation.trace_rows);
+        let stark = CPUStark::<F, D>::new();
+        let mut trace = CPUStark::<F, D>::generate_trace(&simulation);
+        assert_eq!(check_constraints(&stark, &trace, &public_inputs), None);
+
+        // Row 2 executes `Add`, make its `R1` disagree with its bytes
+        trace[COL_REGISTERS + 1].values[2] += F::ONE;
+        assert_eq!(
+            check_constraints(&stark, &trace, &public_inputs),
+            Some((2, word_recomposition_constraint(1)))
+        );
+    }
+
+    #[test]
+    /// Registers beyond `R1` each get their own column
+    fn test_upper_registers_program() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R3, 0x09))
+            .instruction(Instruction::Li(Register::R2, 0x04))
+            .instruction(Instruction::Sub(Register::R3, Register::R2))
+            .instruction(Instruction::Sb(Register::R3, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let (simulation, traces) = simulate_and_trace::<F, D>(&program);
+        let trace = &traces[Table::Cpu as usize];
+        // `Sb` row, after `R3 -= R2`
+        assert_eq!(trace[COL_REGISTERS + 3].values[3], F::from_canonical_u8(5));
+        assert_eq!(trace[COL_MEM_VAL].values[3], F::from_canonical_u8(5));
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    /// The trace has `NUMBER_OF_COLS` columns, one of them per register
+    /// each holding that register on every row
+    fn test_trace_width() {
+        let (simulation, traces) = simulate_and_trace::<F, D>(&fixtures::add());
+        let trace = &traces[Table::Cpu as usize];
+        assert_eq!(trace.len(), NUMBER_OF_COLS);
+        for (row_idx, row) in simulation
+            .trace_rows
+            .iter()
+            .enumerate()
+        {
+            for (reg_idx, &register) in row
+                .registers
+                .iter()
+                .enumerate()
+            {
+                assert_eq!(
+                    trace[COL_REGISTERS + reg_idx].values[row_idx],
+                    F::from_canonical_u32(register)
+                );
+            }
+        }
+    }
+
+    #[test]
+    /// The opcode, recovered from its one-hot encoding, and the operand
+    /// columns are the limbs of the canonical encoding of the executed
+    /// instruction
+    fn test_encoded_instructions() {
+        let (simulation, traces) = simulate_and_trace::<F, D>(&fixtures::add());
+        let trace = &traces[Table::Cpu as usize];
+        let [opcode_weight, operand_a_weight, operand_b_weight] =
+            INSTRUCTION_ENCODING_WEIGHTS.map(F::from_canonical_u64);
+        for (row_idx, row) in simulation
+            .trace_rows
+            .iter()
+            .enumerate()
+        {
+            let opcode = (0..NUM_OPCODE_ONEHOT)
+                .map(|opcode| {
+                    F::from_canonical_usize(opcode)
+                        * trace[COL_OPCODES + opcode].values[row_idx]
+                })
+                .sum::<F>();
+            let encoding = opcode * opcode_weight
+                + trace[COL_OPERAND_A].values[row_idx] * operand_a_weight
+                + trace[COL_OPERAND_B].values[row_idx] * operand_b_weight;
+            assert_eq!(
+                encoding,
+                row.instruction
+                    .encode_to_field::<F>()
+            );
+        }
+    }
+
+    #[test]
+    /// The optional init row leads the table at `clk = 0` with all
+    /// registers `0`, is not executed and has no opcode hot
+    fn test_init_row() {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&fixtures::add());
+        let trace = CPUStark::<F, D>::generate_trace_with_init_row(&simulation);
+
+        assert_eq!(trace[COL_IS_INIT].values[0], F::ONE);
+        assert_eq!(trace[COL_IS_EXEC].values[0], F::ZERO);
+        for col in (COL_CLK..COL_LOC).chain(COL_OPCODES..COL_IS_INIT) {
+            assert_eq!(trace[col].values[0], F::ZERO);
+        }
+        // Followed by the executed rows, starting at `clk = 1`
+        assert_eq!(trace[COL_IS_INIT].values[1], F::ZERO);
+        assert_eq!(trace[COL_IS_EXEC].values[1], F::ONE);
+        assert_eq!(trace[COL_CLK].values[1], F::ONE);
+
+        traces[Table::Cpu as usize] = trace;
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    #[should_panic]
+    /// An init row with a non-zero register breaks the all-zero initial
+    /// state
+    fn test_tampered_init_row() {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&fixtures::add());
+        let mut trace =
+            CPUStark::<F, D>::generate_trace_with_init_row(&simulation);
+        trace[COL_REGISTERS].values[0] = F::ONE;
+
+        traces[Table::Cpu as usize] = trace;
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    /// Registers and memory values past a byte are decomposed into bytes,
+    /// all of which are in the byte table
+    fn test_word_values_program() {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 0x1234_5678))
+            .instruction(Instruction::Not(Register::R0))
+            .instruction(Instruction::Sb(
+                Register::R0,
+                MemoryLocation(0x1_0000),
+            ))
+            .instruction(Instruction::Lb(
+                Register::R1,
+                MemoryLocation(0x1_0000),
+            ))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+
+        let (simulation, traces) = simulate_and_trace::<F, D>(&program);
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    /// `Li R0, 1; Halt; Li R1, 2; Halt` along with the rows of executing its
+    /// first `Halt` and `Li`
+    fn halted_program_rows() -> (Program, Vec<SimulationRow>) {
+        let program = ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R0, 1))
+            .instruction(Instruction::Halt)
+            .instruction(Instruction::Li(Register::R1, 2))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        (program, simulation.trace_rows)
+    }
+
+    #[test]
+    #[should_panic]
+    /// Carrying on past a `Halt`, even into instructions of the program
+    /// and ending in another `Halt`, does not verify
+    fn test_execution_after_halt() {
+        let (program, mut rows) = halted_program_rows();
+        for _ in 0..2 {
+            let next = rows
+                .last()
+                .unwrap()
+                .execute_one_cycle(&program)
+                .unwrap();
+            rows.push(next);
+        }
+        assert_eq!(rows.len(), 4);
+
+        prove_and_verify_traces::<F, C, D>(
+            generate_traces_from_rows::<F, D>(
+                &program,
+                &program.memory_init,
+                &rows,
+            ),
+            &generate_public_inputs_from_rows::<F, D>(&rows),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    /// Stopping before the `Halt` while claiming to end in one does not
+    /// verify
+    fn test_claimed_halt_stopped_early() {
+        let (program, rows) = halted_program_rows();
+        let rows = &rows[..1];
+        let mut public_inputs = generate_public_inputs_from_rows::<F, D>(rows);
+        public_inputs[Table::Cpu as usize][PI_ENDS_IN_HALT] = F::ONE;
+
+        prove_and_verify_traces::<F, C, D>(
+            generate_traces_from_rows::<F, D>(
+                &program,
+                &program.memory_init,
+                rows,
+            ),
+            &public_inputs,
+        );
+    }
+
+    #[test]
+    /// The public inputs tell apart executions ending in a `Halt`
+    fn test_ends_in_halt() {
+        let (_, rows) = halted_program_rows();
+        let ends_in_halt = |rows: &[SimulationRow]| {
+            CPUStark::<F, D>::ends_in_halt(&CPUStark::<F, D>::public_inputs(
+                rows,
+            ))
+            .unwrap()
+        };
+        assert!(ends_in_halt(&rows));
+        assert!(!ends_in_halt(&rows[..1]));
+    }
+
+    /// `Li R1, 0x2a; Mov R0, R1; Sb R0, [0x42]; Halt`
+    fn mov_program() -> Program {
+        ProgramBuilder::new()
+            .instruction(Instruction::Li(Register::R1, 0x2a))
+            .instruction(Instruction::Mov(Register::R0, Register::R1))
+            .instruction(Instruction::Sb(Register::R0, MemoryLocation(0x42)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_mov_program() {
+        let (simulation, traces) = simulate_and_trace::<F, D>(&mov_program());
+        let trace = &traces[Table::Cpu as usize];
+        // Row 1 executes `Mov R0, R1`
+        assert_eq!(trace[COL_MOV_DST].values[1], F::ONE);
+        assert_eq!(trace[COL_MOV_SRC + 1].values[1], F::ONE);
+        assert_eq!(trace[COL_MOV_VAL].values[1], F::from_canonical_u8(0x2a));
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    /// Traces of [mov_program] where the `Sb` row holds `R0 = 0x2b`, as if
+    /// `Mov` had not copied `R1`
+    fn tampered_mov_traces(
+    ) -> (PreflightSimulation, [Vec<PolynomialValues<F>>; NUM_TABLES]) {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&mov_program());
+        let trace = &mut traces[Table::Cpu as usize];
+        // Keep `R0` made up of its bytes, its low byte being the first
+        trace[COL_REGISTERS].values[2] += F::ONE;
+        trace[COL_BYTES].values[2] += F::ONE;
+        (simulation, traces)
+    }
+
+    #[test]
+    /// The wrong result of a `Mov` breaks a constraint on the `Mov` row
+    fn test_tampered_mov_result_constraints() {
+        let (simulation, traces) = tampered_mov_traces();
+        let public_inputs =
+            CPUStark::<F, D>::public_inputs(&simulation.trace_rows);
+        let violation = check_constraints(
+            &CPUStark::<F, D>::new(),
+            &traces[Table::Cpu as usize],
+            &public_inputs,
+        );
+        assert_eq!(violation.map(|(row, _)| row), Some(1));
+    }
+
+    #[test]
+    #[should_panic]
+    /// A `Mov` whose destination does not end up holding the source does
+    /// not verify
+    fn test_tampered_mov_result() {
+        let (simulation, traces) = tampered_mov_traces();
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    #[should_panic]
+    /// A `PC` outside of the program is not in the program table, so the
+    /// program counter lookup fails
+    fn test_out_of_program_pc() {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&fixtures::add());
+        // Row 2 executes `Add` at `PC = 2`, the program ends at `PC = 4`
+        traces[Table::Cpu as usize][COL_PC].values[2] =
+            F::from_canonical_u8(0x80);
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    /// With the instruction at the out-of-program `PC` added to the program
+    /// table as well, the lookups link up again. The table then no longer
+    /// holds the program, which the verifier catches.
+    fn test_out_of_program_pc_in_extended_program() {
+        let program = fixtures::add();
+        let mut rows = PreflightSimulation::simulate(&program)
+            .unwrap()
+            .trace_rows;
+        // Row 2 executes `Add` at `PC = 2`, move it to `PC = 0x80`
+        rows[2].program_counter = 0x80;
+        let mut extended = program.clone();
+        extended
+            .code
+            .insert(0x80, rows[2].instruction.clone());
+
+        let zkvm = PixieZKVM::<F, D>::new();
+        let proof = prove_all::<F, C, D>(
+            &zkvm,
+            StarkSet::<F>::from_rows::<D>(
+                &extended,
+                &program.memory_init,
+                &rows,
+            ),
+        )
+        .unwrap();
+        let verify_against = |prog: &Program| {
+            verify_all(
+                &zkvm,
+                &proof,
+                &known_program_columns::<F, D>(
+                    prog,
+                    Some(&prog.memory_init),
+                    None,
+                ),
+            )
+        };
+        assert!(verify_against(&extended).is_ok());
+        assert!(verify_against(&program).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    /// A "byte" of `256` still adds up to its register, but it is not in
+    /// the byte table, so the range check lookup fails
+    fn test_out_of_range_byte() {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&fixtures::add());
+        let trace = &mut traces[Table::Cpu as usize];
+
+        // Row 2 executes `Add`, make its `R1` a `256` held in one byte
+        trace[COL_REGISTERS + 1].values[2] = F::from_canonical_u16(256);
+        for byte in 0..WORD_BYTES {
+            trace[COL_BYTES + WORD_BYTES + byte].values[2] = F::ZERO;
+        }
+        trace[COL_BYTES + WORD_BYTES].values[2] = F::from_canonical_u16(256);
+
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    #[should_panic]
+    /// Flipping two unset opcode bits on the `Add` row breaks the one-hot
+    /// encoding, so either proving or verification has to fail
+    fn test_tampered_opcode_bits() {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&fixtures::add());
+        let trace = &mut traces[Table::Cpu as usize];
+
+        // Row 2 executes `Add`, additionally mark it as `Mul` and `Div`
+        trace[COL_OPCODES + 2].values[2] = F::ONE;
+        trace[COL_OPCODES + 3].values[2] = F::ONE;
+
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    #[should_panic]
+    /// A loaded value the memory table never saw breaks the CPU <-> Memory
+    /// lookup
+    fn test_fabricated_load_value() {
+        let (simulation, mut traces) =
+            simulate_and_trace::<F, D>(&fixtures::add());
+        let trace = &mut traces[Table::Cpu as usize];
+
+        // Row 0 executes `Lb` of `0x40`
+        trace[COL_MEM_VAL].values[0] += F::ONE;
+
+        prove_and_verify::<F, C, D>(&simulation, traces);
+    }
+
+    #[test]
+    /// The recursive constraints build into a circuit, so that this table
+    /// can be verified inside another plonky2 circuit
+    fn test_circuit_constraints() {
+        let stark = CPUStark::<F, D>::new();
+        assert!(test_stark_circuit_constraints::<F, C, _, D>(stark).is_ok());
+    }
+
+    #[test]
+    fn test_column_docs() {
+        assert_eq!(
+            CPUStark::<F, D>::column_docs().len(),
+            CPUStark::<F, D>::COLUMNS
         );
-        assert!(proof.is_ok());
-        let verification = verify_stark_proof(stark, proof.unwrap(), &config);
-        assert!(verification.is_ok());
     }
 }
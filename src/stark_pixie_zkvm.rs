@@ -1,29 +1,90 @@
-use anyhow::Result;
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
+
+use anyhow::{
+    anyhow,
+    Context,
+    Result,
+};
 use plonky2::{
     field::{
         extension::Extendable,
         polynomial::PolynomialValues,
+        types::Field,
     },
     fri::oracle::PolynomialBatch,
     hash::{
-        hash_types::RichField,
+        hash_types::{
+            HashOut,
+            RichField,
+        },
         merkle_tree::MerkleCap,
+        poseidon::PoseidonHash,
     },
     iop::challenger::Challenger,
     plonk::config::{
         AlgebraicHasher,
         GenericConfig,
+        Hasher,
     },
     util::timing::TimingTree,
 };
-use starky::config::StarkConfig;
+use starky::{
+    config::StarkConfig,
+    cross_table_lookup::{
+        get_ctl_data,
+        get_ctl_vars_from_proofs,
+        verify_cross_table_lookups,
+        CrossTableLookup,
+        CtlCheckVars,
+        CtlData,
+        TableWithColumns,
+    },
+    lookup::{
+        get_grand_product_challenge_set,
+        GrandProductChallengeSet,
+    },
+    proof::{
+        MultiProof,
+        StarkProofWithMetadata,
+    },
+    prover::prove_with_commitment,
+    stark::Stark,
+    verifier::verify_stark_proof_with_challenges,
+};
 
 use crate::{
-    preflight_simulator::PreflightSimulation,
-    stark_cpu::CPUStark,
-    stark_memory::MemoryStark,
-    stark_program_instructions::ProgramInstructionsStark,
-    vm_specs::Program,
+    assembler::assemble,
+    preflight_simulator::{
+        PreflightSimulation,
+        SimulationRow,
+    },
+    stark_cpu::{
+        self,
+        CPUStark,
+        NUM_STATE_COLS,
+    },
+    stark_memory::{
+        self,
+        MemoryStark,
+    },
+    stark_program_instructions::{
+        self,
+        ProgramInstructionsStark,
+        MEMORY_FINAL_COLS,
+        MEMORY_INIT_COLS,
+    },
+    stark_rangecheck_u8::{
+        self,
+        RangeCheckU8Stark,
+    },
+    vm_specs::{
+        Program,
+        Word,
+        REGISTER_COUNT,
+    },
 };
 
 /// STARK Gadgets of Pixie ZKVM
@@ -38,6 +99,856 @@ where
     pub program_instructions: ProgramInstructionsStark<F, D>,
     pub cpu: CPUStark<F, D>,
     pub memory: MemoryStark<F, D>,
+    pub range_check_u8: RangeCheckU8Stark<F, D>,
+    pub cross_table_lookups: Vec<CrossTableLookup<F>>,
+}
+
+impl<F, const D: usize> PixieZKVM<F, D>
+where
+    F: RichField + Extendable<D>,
+{
+    pub fn new() -> Self {
+        Self {
+            program_instructions: ProgramInstructionsStark::new(),
+            cpu: CPUStark::new(),
+            memory: MemoryStark::new(),
+            range_check_u8: RangeCheckU8Stark::new(),
+            cross_table_lookups: all_cross_table_lookups(),
+        }
+    }
+
+    pub fn max_constraint_degree(&self) -> usize {
+        [
+            self.program_instructions
+                .constraint_degree(),
+            self.cpu
+                .constraint_degree(),
+            self.memory
+                .constraint_degree(),
+            self.range_check_u8
+                .constraint_degree(),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default()
+    }
+
+    /// Opcodes whose semantics the proofs constrain. A proof of a program
+    /// executing any other opcode shows the program ran, but not that it
+    /// computed the right values, see [Self::unconstrained_opcodes].
+    pub fn constrained_opcodes() -> BTreeSet<u8> {
+        BTreeSet::from(stark_cpu::CONSTRAINED_OPCODES)
+    }
+
+    /// Opcodes in the code of `prog` missing from
+    /// [Self::constrained_opcodes]. Proofs of `prog` are only fully
+    /// trustworthy if this is empty.
+    pub fn unconstrained_opcodes(prog: &Program) -> BTreeSet<u8> {
+        let constrained = Self::constrained_opcodes();
+        prog.code
+            .values()
+            .map(|instruction| instruction.get_opcode())
+            .filter(|opcode| !constrained.contains(opcode))
+            .collect()
+    }
+}
+
+/// Index of each STARK table in the per-table arrays of traces and proofs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    ProgramInstructions = 0,
+    Cpu = 1,
+    Memory = 2,
+    RangeCheckU8 = 3,
+}
+
+pub const NUM_TABLES: usize = 4;
+
+/// Every `Lb` / `Sb` executed by the CPU must be a row of the memory
+/// table with the same `(clock, address, value)`
+pub fn ctl_cpu_memory<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Cpu as usize,
+            stark_cpu::ctl_data_memory(),
+            stark_cpu::ctl_filter_memory(),
+        )],
+        TableWithColumns::new(
+            Table::Memory as usize,
+            stark_memory::ctl_data_cpu(),
+            stark_memory::ctl_filter_cpu(),
+        ),
+    )
+}
+
+/// Every instruction executed by the CPU must be part of the program,
+/// compared by `(pc, instruction_data)`
+pub fn ctl_cpu_program<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Cpu as usize,
+            stark_cpu::ctl_data_program(),
+            stark_cpu::ctl_filter_program(),
+        )],
+        TableWithColumns::new(
+            Table::ProgramInstructions as usize,
+            stark_program_instructions::ctl_data_cpu(),
+            stark_program_instructions::ctl_filter_cpu(),
+        ),
+    )
+}
+
+/// Every `PC` executed by the CPU must be the location of an instruction
+/// of the program. [ctl_cpu_program] implies this already, but spelling it
+/// out keeps jump targets from pointing outside the code even if the
+/// encoding of instructions changes.
+pub fn ctl_cpu_program_counter<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Cpu as usize,
+            stark_cpu::ctl_data_program_counter(),
+            stark_cpu::ctl_filter_program(),
+        )],
+        TableWithColumns::new(
+            Table::ProgramInstructions as usize,
+            stark_program_instructions::ctl_data_program_counter(),
+            stark_program_instructions::ctl_filter_cpu(),
+        ),
+    )
+}
+
+/// Every init row of the memory table, but for the implicit ones, must be
+/// a cell of the initial memory held by the program table, and the other
+/// way around
+pub fn ctl_memory_program_init<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Memory as usize,
+            stark_memory::ctl_data_memory_init(),
+            stark_memory::ctl_filter_memory_init(),
+        )],
+        TableWithColumns::new(
+            Table::ProgramInstructions as usize,
+            stark_program_instructions::ctl_data_memory_init(),
+            stark_program_instructions::ctl_filter_memory_init(),
+        ),
+    )
+}
+
+/// The last access of every address of the memory table must be a cell of
+/// the final memory held by the program table, and the other way around.
+/// This is the memory a chunk hands over to the next one, see
+/// [ChunkedProof].
+pub fn ctl_memory_program_final<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Memory as usize,
+            stark_memory::ctl_data_memory_final(),
+            stark_memory::ctl_filter_memory_final(),
+        )],
+        TableWithColumns::new(
+            Table::ProgramInstructions as usize,
+            stark_program_instructions::ctl_data_memory_final(),
+            stark_program_instructions::ctl_filter_memory_final(),
+        ),
+    )
+}
+
+/// Every byte of the registers and memory values seen by the CPU and
+/// the memory tables must be in the byte table, which makes all of them
+/// words rather than arbitrary field elements
+pub fn ctl_range_check_u8<F: Field>() -> CrossTableLookup<F> {
+    let cpu = stark_cpu::ctl_data_range_check()
+        .into_iter()
+        .map(|columns| {
+            TableWithColumns::new(
+                Table::Cpu as usize,
+                columns,
+                stark_cpu::ctl_filter_range_check(),
+            )
+        });
+    let memory = stark_memory::ctl_data_range_check()
+        .into_iter()
+        .map(|columns| {
+            TableWithColumns::new(
+                Table::Memory as usize,
+                columns,
+                stark_memory::ctl_filter_range_check(),
+            )
+        });
+    CrossTableLookup::new(
+        cpu.chain(memory)
+            .collect(),
+        TableWithColumns::new(
+            Table::RangeCheckU8 as usize,
+            stark_rangecheck_u8::ctl_data(),
+            stark_rangecheck_u8::ctl_filter(),
+        ),
+    )
+}
+
+/// All the cross-table lookups tying the tables of the VM together
+pub fn all_cross_table_lookups<F: Field>() -> Vec<CrossTableLookup<F>> {
+    vec![
+        ctl_cpu_memory(),
+        ctl_cpu_program(),
+        ctl_cpu_program_counter(),
+        ctl_memory_program_init(),
+        ctl_memory_program_final(),
+        ctl_range_check_u8(),
+    ]
+}
+
+/// Traces shorter than this are proven with a lowered `cap_height`, see
+/// [config_for_trace_len]
+pub const SHORT_TRACE_LEN: usize = 1 << 5;
+
+/// Configuration for proving traces of `trace_len` rows. Starts from
+/// `standard_fast_config`, only lowering `cap_height` for traces that
+/// pad to fewer than [SHORT_TRACE_LEN] rows, whose Merkle trees are too
+/// small for the default caps. Number of cap = 2^{cap_height}.
+pub fn config_for_trace_len(trace_len: usize) -> StarkConfig {
+    let mut config = StarkConfig::standard_fast_config();
+    if trace_len.next_power_of_two() < SHORT_TRACE_LEN {
+        config
+            .fri_config
+            .cap_height = 1;
+    }
+    config
+}
+
+/// Configuration for proving `traces`. All tables are proven with the
+/// same configuration, so it is picked for the shortest of them.
+pub fn config_for_traces<F>(
+    traces: &[Vec<PolynomialValues<F>>; NUM_TABLES]
+) -> StarkConfig
+where
+    F: Field,
+{
+    let shortest = traces
+        .iter()
+        .map(|trace| {
+            trace
+                .first()
+                .map_or(0, |column| column.len())
+        })
+        .min()
+        .unwrap_or_default();
+    config_for_trace_len(shortest)
+}
+
+/// Configuration `proof` was generated with, see [config_for_traces]. The
+/// `cap_height` is read off the trace caps, then checked against the trace
+/// lengths the proof is for.
+pub fn config_for_proof<F, C, const D: usize>(
+    proof: &PixieProof<F, C, D>
+) -> Result<StarkConfig>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let stark_proofs = &proof
+        .multi_proof
+        .stark_proofs;
+    let cap_len = stark_proofs[Table::Cpu as usize]
+        .proof
+        .trace_cap
+        .0
+        .len();
+    if !cap_len.is_power_of_two() {
+        return Err(anyhow!("trace cap of {} hashes", cap_len));
+    }
+
+    let mut config = StarkConfig::standard_fast_config();
+    config
+        .fri_config
+        .cap_height = cap_len.trailing_zeros() as usize;
+    let shortest = stark_proofs
+        .iter()
+        .map(|stark_proof| {
+            let degree_bits = stark_proof
+                .proof
+                .recover_degree_bits(&config);
+            1usize
+                .checked_shl(degree_bits as u32)
+                .unwrap_or(usize::MAX)
+        })
+        .min()
+        .unwrap_or_default();
+    if config_for_trace_len(shortest)
+        .fri_config
+        .cap_height
+        != config
+            .fri_config
+            .cap_height
+    {
+        return Err(anyhow!("cap height does not match the trace lengths"));
+    }
+    Ok(config)
+}
+
+/// Traces of all the tables, indexed by [Table]
+pub fn generate_traces<F, const D: usize>(
+    prog: &Program,
+    simulation: &PreflightSimulation,
+) -> [Vec<PolynomialValues<F>>; NUM_TABLES]
+where
+    F: RichField + Extendable<D>,
+{
+    generate_traces_from_rows(
+        prog,
+        &simulation.memory_init,
+        &simulation.trace_rows,
+    )
+}
+
+/// Traces of all the tables for a contiguous run of `rows` starting from
+/// the memory state `memory_init`, indexed by [Table]
+pub fn generate_traces_from_rows<F, const D: usize>(
+    prog: &Program,
+    memory_init: &HashMap<Word, Word>,
+    rows: &[SimulationRow],
+) -> [Vec<PolynomialValues<F>>; NUM_TABLES]
+where
+    F: RichField + Extendable<D>,
+{
+    let cpu = CPUStark::<F, D>::generate_trace_from_rows(rows);
+    let memory =
+        MemoryStark::<F, D>::generate_trace_from_rows(memory_init, rows);
+    let range_check_u8 = RangeCheckU8Stark::<F, D>::generate_trace(
+        stark_cpu::range_checked_values(&cpu)
+            .into_iter()
+            .chain(stark_memory::range_checked_values(&memory)),
+    );
+    [
+        ProgramInstructionsStark::<F, D>::generate_trace(
+            prog,
+            memory_init,
+            &MemoryStark::<F, D>::final_memory(memory_init, rows),
+            rows,
+        ),
+        cpu,
+        memory,
+        range_check_u8,
+    ]
+}
+
+/// Public inputs of all the tables, indexed by [Table]
+pub fn generate_public_inputs<F, const D: usize>(
+    simulation: &PreflightSimulation
+) -> [Vec<F>; NUM_TABLES]
+where
+    F: RichField + Extendable<D>,
+{
+    generate_public_inputs_from_rows::<F, D>(&simulation.trace_rows)
+}
+
+/// Public inputs of all the tables for a contiguous run of `rows`,
+/// indexed by [Table]
+pub fn generate_public_inputs_from_rows<F, const D: usize>(
+    rows: &[SimulationRow]
+) -> [Vec<F>; NUM_TABLES]
+where
+    F: RichField + Extendable<D>,
+{
+    [
+        vec![],
+        CPUStark::<F, D>::public_inputs(rows).to_vec(),
+        MemoryStark::<F, D>::public_inputs(&[]).to_vec(),
+        vec![],
+    ]
+}
+
+/// Traces and public inputs of all the tables of the VM, indexed by
+/// [Table], i.e. everything [prove_all] needs to prove them together
+pub struct StarkSet<F: Field> {
+    pub traces: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    pub public_inputs: [Vec<F>; NUM_TABLES],
+}
+
+impl<F: RichField> StarkSet<F> {
+    /// Tables of the execution `simulation` of `prog`, see
+    /// [generate_traces] and [generate_public_inputs]
+    pub fn from_simulation<const D: usize>(
+        prog: &Program,
+        simulation: &PreflightSimulation,
+    ) -> Self
+    where
+        F: Extendable<D>,
+    {
+        Self {
+            traces: generate_traces::<F, D>(prog, simulation),
+            public_inputs: generate_public_inputs::<F, D>(simulation),
+        }
+    }
+
+    /// Tables of a contiguous run of `rows` starting from the memory state
+    /// `memory_init`, see [generate_traces_from_rows]
+    pub fn from_rows<const D: usize>(
+        prog: &Program,
+        memory_init: &HashMap<Word, Word>,
+        rows: &[SimulationRow],
+    ) -> Self
+    where
+        F: Extendable<D>,
+    {
+        Self {
+            traces: generate_traces_from_rows::<F, D>(prog, memory_init, rows),
+            public_inputs: generate_public_inputs_from_rows::<F, D>(rows),
+        }
+    }
+}
+
+/// Values an execution ends with, as attested by a [PixieProof]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PixiePublicValues {
+    /// Registers after the last executed instruction
+    pub final_registers: [Word; REGISTER_COUNT],
+    /// `(address, value)` of the memory cells claimed as outputs, in the
+    /// order given to [generate_proof_with_outputs]
+    pub final_memory: Vec<(Word, Word)>,
+}
+
+impl PixiePublicValues {
+    /// Public values read off the public inputs of all the tables
+    pub fn from_public_inputs<F, const D: usize>(
+        public_inputs: &[Vec<F>; NUM_TABLES]
+    ) -> Result<Self>
+    where
+        F: RichField + Extendable<D>,
+    {
+        Ok(Self {
+            final_registers: CPUStark::<F, D>::final_registers(
+                &public_inputs[Table::Cpu as usize],
+            )?,
+            final_memory: MemoryStark::<F, D>::outputs_from_public_inputs(
+                &public_inputs[Table::Memory as usize],
+            )?,
+        })
+    }
+}
+
+/// Proof of an execution, covering all the tables of the VM
+#[derive(Clone)]
+pub struct PixieProof<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    /// Proofs of all the tables, indexed by [Table]. Each of them carries
+    /// the state of the shared Fiat-Shamir transcript it was started from.
+    pub multi_proof: MultiProof<F, C, D, NUM_TABLES>,
+    /// Public inputs of all the tables, indexed by [Table]
+    pub public_inputs: [Vec<F>; NUM_TABLES],
+    /// Challenges of the cross-table lookups, the first thing sampled
+    /// from the transcript after observing all trace caps and public
+    /// inputs
+    pub ctl_challenges: GrandProductChallengeSet<F>,
+}
+
+/// Proves all the tables of `tables` with the configuration picked for
+/// their traces, see [config_for_traces]. This is the one entry point all
+/// proofs go through, see [prove_with_ctls] for the transcript.
+pub fn prove_all<F, C, const D: usize>(
+    zkvm: &PixieZKVM<F, D>,
+    tables: StarkSet<F>,
+) -> Result<PixieProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let config = config_for_traces(&tables.traces);
+    prove_with_ctls(
+        zkvm,
+        &config,
+        tables.traces,
+        &tables.public_inputs,
+        &mut TimingTree::default(),
+    )
+}
+
+/// Counterpart of [prove_all], with the configuration read off `proof`,
+/// see [config_for_proof] and [verify_with_ctls]
+pub fn verify_all<F, C, const D: usize>(
+    zkvm: &PixieZKVM<F, D>,
+    proof: &PixieProof<F, C, D>,
+    known_program_columns: &[(usize, PolynomialValues<F>)],
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    verify_with_ctls(
+        zkvm,
+        &config_for_proof(proof)?,
+        proof,
+        known_program_columns,
+    )
+}
+
+/// Proves all the tables together with the cross-table lookups between
+/// them. All tables share one Fiat-Shamir transcript which observes the
+/// trace caps in [Table] order, followed by the public inputs.
+pub fn prove_with_ctls<F, C, const D: usize>(
+    zkvm: &PixieZKVM<F, D>,
+    config: &StarkConfig,
+    traces: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_inputs: &[Vec<F>; NUM_TABLES],
+    timing: &mut TimingTree,
+) -> Result<PixieProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let commitments = traces
+        .iter()
+        .map(|trace| {
+            PolynomialBatch::<F, C, D>::from_values(
+                trace.clone(),
+                config
+                    .fri_config
+                    .rate_bits,
+                false,
+                config
+                    .fri_config
+                    .cap_height,
+                timing,
+                None,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    for commitment in &commitments {
+        challenger.observe_cap(
+            &commitment
+                .merkle_tree
+                .cap,
+        );
+    }
+    for table_public_inputs in public_inputs {
+        challenger.observe_elements(table_public_inputs);
+    }
+
+    let (ctl_challenges, ctl_data) = get_ctl_data::<F, C, D, NUM_TABLES>(
+        config,
+        &traces,
+        &zkvm.cross_table_lookups,
+        &mut challenger,
+        zkvm.max_constraint_degree(),
+    );
+
+    let program_instructions = prove_table(
+        &zkvm.program_instructions,
+        config,
+        &traces[Table::ProgramInstructions as usize],
+        &commitments[Table::ProgramInstructions as usize],
+        &ctl_data[Table::ProgramInstructions as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::ProgramInstructions as usize],
+        timing,
+    )?;
+    let cpu = prove_table(
+        &zkvm.cpu,
+        config,
+        &traces[Table::Cpu as usize],
+        &commitments[Table::Cpu as usize],
+        &ctl_data[Table::Cpu as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::Cpu as usize],
+        timing,
+    )?;
+    let memory = prove_table(
+        &zkvm.memory,
+        config,
+        &traces[Table::Memory as usize],
+        &commitments[Table::Memory as usize],
+        &ctl_data[Table::Memory as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::Memory as usize],
+        timing,
+    )?;
+    let range_check_u8 = prove_table(
+        &zkvm.range_check_u8,
+        config,
+        &traces[Table::RangeCheckU8 as usize],
+        &commitments[Table::RangeCheckU8 as usize],
+        &ctl_data[Table::RangeCheckU8 as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::RangeCheckU8 as usize],
+        timing,
+    )?;
+
+    Ok(PixieProof {
+        multi_proof: MultiProof {
+            stark_proofs: [program_instructions, cpu, memory, range_check_u8],
+        },
+        public_inputs: public_inputs.clone(),
+        ctl_challenges,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prove_table<F, C, S, const D: usize>(
+    stark: &S,
+    config: &StarkConfig,
+    trace: &[PolynomialValues<F>],
+    commitment: &PolynomialBatch<F, C, D>,
+    ctl_data: &CtlData<F>,
+    ctl_challenges: &GrandProductChallengeSet<F>,
+    challenger: &mut Challenger<F, C::Hasher>,
+    public_inputs: &[F],
+    timing: &mut TimingTree,
+) -> Result<StarkProofWithMetadata<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    let init_challenger_state = challenger.compact();
+    let proof = prove_with_commitment(
+        stark,
+        config,
+        trace,
+        commitment,
+        Some(ctl_data),
+        Some(ctl_challenges),
+        challenger,
+        public_inputs,
+        timing,
+    )?;
+    Ok(StarkProofWithMetadata {
+        init_challenger_state,
+        proof: proof.proof,
+    })
+}
+
+/// Counterpart of [prove_with_ctls], replays the same transcript. The
+/// program table is checked to hold `known_program_columns`, see
+/// [known_program_columns], which is what ties the proof to a program.
+pub fn verify_with_ctls<F, C, const D: usize>(
+    zkvm: &PixieZKVM<F, D>,
+    config: &StarkConfig,
+    proof: &PixieProof<F, C, D>,
+    known_program_columns: &[(usize, PolynomialValues<F>)],
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let public_inputs = &proof.public_inputs;
+    let proof_ctl_challenges = &proof.ctl_challenges;
+    let proof = &proof.multi_proof;
+
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    for stark_proof in &proof.stark_proofs {
+        challenger.observe_cap(
+            &stark_proof
+                .proof
+                .trace_cap,
+        );
+    }
+    for table_public_inputs in public_inputs {
+        challenger.observe_elements(table_public_inputs);
+    }
+
+    // Caps observed in any other order, or for other traces, lead to a
+    // different transcript and hence to different challenges
+    let ctl_challenges =
+        get_grand_product_challenge_set(&mut challenger, config.num_challenges);
+    if &ctl_challenges != proof_ctl_challenges {
+        return Err(anyhow!(
+            "cross-table lookup challenges do not match the transcript"
+        ));
+    }
+    let num_lookup_columns = [
+        zkvm.program_instructions
+            .num_lookup_helper_columns(config),
+        zkvm.cpu
+            .num_lookup_helper_columns(config),
+        zkvm.memory
+            .num_lookup_helper_columns(config),
+        zkvm.range_check_u8
+            .num_lookup_helper_columns(config),
+    ];
+    let ctl_vars = get_ctl_vars_from_proofs(
+        proof,
+        &zkvm.cross_table_lookups,
+        &ctl_challenges,
+        &num_lookup_columns,
+        zkvm.max_constraint_degree(),
+    );
+
+    let program_zeta = verify_table(
+        &zkvm.program_instructions,
+        config,
+        &proof.stark_proofs[Table::ProgramInstructions as usize],
+        &ctl_vars[Table::ProgramInstructions as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::ProgramInstructions as usize],
+    )?;
+    verify_known_columns(
+        config,
+        &proof.stark_proofs[Table::ProgramInstructions as usize],
+        program_zeta,
+        known_program_columns,
+    )?;
+    verify_table(
+        &zkvm.cpu,
+        config,
+        &proof.stark_proofs[Table::Cpu as usize],
+        &ctl_vars[Table::Cpu as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::Cpu as usize],
+    )?;
+    verify_table(
+        &zkvm.memory,
+        config,
+        &proof.stark_proofs[Table::Memory as usize],
+        &ctl_vars[Table::Memory as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::Memory as usize],
+    )?;
+    verify_table(
+        &zkvm.range_check_u8,
+        config,
+        &proof.stark_proofs[Table::RangeCheckU8 as usize],
+        &ctl_vars[Table::RangeCheckU8 as usize],
+        &ctl_challenges,
+        &mut challenger,
+        &public_inputs[Table::RangeCheckU8 as usize],
+    )?;
+
+    verify_cross_table_lookups::<F, D, NUM_TABLES>(
+        &zkvm.cross_table_lookups,
+        proof
+            .stark_proofs
+            .clone()
+            .map(|stark_proof| {
+                stark_proof
+                    .proof
+                    .openings
+                    .ctl_zs_first
+                    .unwrap_or_default()
+            }),
+        None,
+        config,
+    )
+}
+
+/// Verifies the proof of one table, returning the point `zeta` its trace
+/// is opened at
+fn verify_table<F, C, S, const D: usize>(
+    stark: &S,
+    config: &StarkConfig,
+    proof: &StarkProofWithMetadata<F, C, D>,
+    ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
+    ctl_challenges: &GrandProductChallengeSet<F>,
+    challenger: &mut Challenger<F, C::Hasher>,
+    public_inputs: &[F],
+) -> Result<F::Extension>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    challenger.compact();
+    let challenges = proof
+        .proof
+        .get_challenges(challenger, Some(ctl_challenges), true, config);
+    verify_stark_proof_with_challenges(
+        stark,
+        &proof.proof,
+        &challenges,
+        Some(ctl_vars),
+        public_inputs,
+        config,
+    )?;
+    Ok(challenges.stark_zeta)
+}
+
+/// Columns of the program table a verifier of an execution of `prog`
+/// knows the values of, see `stark_program_instructions::KNOWN_COLS`. The
+/// initial and final memory columns are left out unless `memory_init` and
+/// `memory_final` are given.
+pub fn known_program_columns<F, const D: usize>(
+    prog: &Program,
+    memory_init: Option<&HashMap<Word, Word>>,
+    memory_final: Option<&HashMap<Word, Word>>,
+) -> Vec<(usize, PolynomialValues<F>)>
+where
+    F: RichField + Extendable<D>,
+{
+    let no_memory = HashMap::new();
+    ProgramInstructionsStark::<F, D>::generate_trace(
+        prog,
+        memory_init.unwrap_or(&no_memory),
+        memory_final.unwrap_or(&no_memory),
+        &[],
+    )
+    .into_iter()
+    .enumerate()
+    .filter(|(col, _)| {
+        stark_program_instructions::KNOWN_COLS.contains(col)
+            && (memory_init.is_some() || !MEMORY_INIT_COLS.contains(col))
+            && (memory_final.is_some() || !MEMORY_FINAL_COLS.contains(col))
+    })
+    .collect()
+}
+
+/// Checks the openings at `zeta` of the trace `proof` is for against the
+/// `known_columns`. As `zeta` is drawn after the trace is committed to, a
+/// column matching its known values at `zeta` matches them on every row
+/// but with negligible probability. The known columns are padded with
+/// zeroes to the length of the trace.
+fn verify_known_columns<F, C, const D: usize>(
+    config: &StarkConfig,
+    proof: &StarkProofWithMetadata<F, C, D>,
+    zeta: F::Extension,
+    known_columns: &[(usize, PolynomialValues<F>)],
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let trace_len = 1usize << proof
+        .proof
+        .recover_degree_bits(config);
+    let openings = &proof
+        .proof
+        .openings
+        .local_values;
+    for (col, column) in known_columns {
+        if column.len() > trace_len {
+            return Err(anyhow!("program table is shorter than the program"));
+        }
+        let mut values = column
+            .values
+            .clone();
+        values.resize(trace_len, F::ZERO);
+        let expected = PolynomialValues::new(values)
+            .ifft()
+            .to_extension::<D>()
+            .eval(zeta);
+        if openings.get(*col) != Some(&expected) {
+            return Err(anyhow!(
+                "column {} of the program table does not match the program",
+                col
+            ));
+        }
+    }
+    Ok(())
 }
 
 pub fn trace_to_merkle_caps<F, C, const D: usize>(
@@ -66,49 +977,585 @@ where
     .cap
 }
 
-pub fn generate_proof<F, C, const D: usize>(prog: &Program) -> Result<()>
+/// Simulates `prog` and proves its execution. Programs without code
+/// still prove, but the proof says nothing about any execution, see
+/// `PreflightSimulation::is_trivial`.
+pub fn generate_proof<F, C, const D: usize>(
+    prog: &Program
+) -> Result<PixieProof<F, C, D>>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
     <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
 {
-    //type PR = StarkProofWithPublicInputs<GoldilocksField, C, D>;
-
-    let mut config = StarkConfig::standard_fast_config();
-    // Need to do this since our table can be small.
-    config
-        .fri_config
-        .cap_height = 1;
+    generate_proof_with_outputs(prog, &[])
+}
 
+/// Like [generate_proof], additionally claiming the final values of the
+/// memory cells at `outputs` in the [PixiePublicValues] of the proof. At
+/// most `stark_memory::MAX_OUTPUT_CELLS` cells can be claimed, each of
+/// which must be initialized or stored to by the program.
+pub fn generate_proof_with_outputs<F, C, const D: usize>(
+    prog: &Program,
+    outputs: &[Word],
+) -> Result<PixieProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
     // Do a simulation
     let simulation = PreflightSimulation::simulate(prog)?;
+    prove_simulation(prog, &simulation, outputs)
+}
+
+/// Assembles `src`, see `assembler::assemble`, then simulates and proves
+/// the resulting program like [generate_proof]. Errors name the stage
+/// they come from.
+pub fn assemble_and_prove<F, C, const D: usize>(
+    src: &str
+) -> Result<PixieProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let prog = assemble(src).context("assembling the program")?;
+    let simulation = PreflightSimulation::simulate(&prog)
+        .context("simulating the program")?;
+    prove_simulation(&prog, &simulation, &[]).context("proving the execution")
+}
+
+/// Proves the execution of `prog` recorded in `simulation`, claiming the
+/// final values of the memory cells at `outputs`
+fn prove_simulation<F, C, const D: usize>(
+    prog: &Program,
+    simulation: &PreflightSimulation,
+    outputs: &[Word],
+) -> Result<PixieProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let zkvm = PixieZKVM::<F, D>::new();
+
+    if outputs.len() > stark_memory::MAX_OUTPUT_CELLS {
+        return Err(anyhow!(
+            "at most {} memory cells can be claimed as outputs",
+            stark_memory::MAX_OUTPUT_CELLS
+        ));
+    }
+    // Memory as left by the execution. The last row is a `Halt`, so its
+    // snapshot is the final state.
+    let final_memory = match simulation
+        .trace_rows
+        .last()
+    {
+        Some(row) => row
+            .memory_snapshot
+            .clone(),
+        None => im::HashMap::from(&simulation.memory_init),
+    };
+    let claimed = outputs
+        .iter()
+        .map(|&address| {
+            final_memory
+                .get(&address)
+                .map(|&value| (address, value))
+                .ok_or_else(|| {
+                    anyhow!("output {:#x} is never written to", address)
+                })
+        })
+        .collect::<Result<Vec<(Word, Word)>>>()?;
 
     // Generate traces for each of the STARK tables
-    let pi_trace = ProgramInstructionsStark::<F, D>::generate_trace(prog);
-    let cpu_trace = CPUStark::<F, D>::generate_trace(&simulation);
-    let mem_trace = MemoryStark::<F, D>::generate_trace(&simulation);
-
-    // Commit to each of the tables
-    let pi_comm_cap = trace_to_merkle_caps::<F, C, D>(&config, &pi_trace);
-    let cpu_comm_cap = trace_to_merkle_caps::<F, C, D>(&config, &cpu_trace);
-    let mem_comm_cap = trace_to_merkle_caps::<F, C, D>(&config, &mem_trace);
-
-    // Create a new IOP challenger and let it observe all the commitments
-    // This is Fiat-Shamir!
-    // This challenger needs to be reproduced at the verifier's end as well
-    // so make sure all the inputs are available to the verifier. We are putting
-    // in commitments and not the traces directly as the latter is not available
-    // in full to the verifier
-    let mut iop_challenger = Challenger::<F, C::Hasher>::new();
-    iop_challenger.observe_cap(&pi_comm_cap);
-    iop_challenger.observe_cap(&cpu_comm_cap);
-    iop_challenger.observe_cap(&mem_comm_cap);
-
-    // Get `config.num_challenges` number of grand product challenge points
-    // Each grand product challenge requires two elements in `F`: `beta` and
-    // `gamma`. Hence, `2 * config.num_challenges` sampled
-    let grand_product_challenges =
-        iop_challenger.get_n_challenges(2 * config.num_challenges);
+    let mut tables = StarkSet::<F>::from_simulation::<D>(prog, simulation);
+    MemoryStark::<F, D>::mark_outputs(
+        &mut tables.traces[Table::Memory as usize],
+        outputs,
+    );
+    tables.public_inputs[Table::Memory as usize] =
+        MemoryStark::<F, D>::public_inputs(&claimed).to_vec();
+
+    // Commitments to the tables are observed by one shared Fiat-Shamir
+    // transcript, from which the cross-table lookup challenges and the
+    // challenges of each of the tables are drawn
+    prove_all(&zkvm, tables)
+}
+
+/// Verifies `proof` to be a proof of an execution of `prog`, see
+/// [generate_proof]. Returns the values the execution provably ends with.
+/// The program table of the proof has to hold the code and the initial
+/// memory of `prog`, see [known_program_columns].
+pub fn verify_proof<F, C, const D: usize>(
+    proof: &PixieProof<F, C, D>,
+    prog: &Program,
+) -> Result<PixiePublicValues>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let zkvm = PixieZKVM::<F, D>::new();
+
+    // The execution has to start where `prog` starts
+    if !prog
+        .code
+        .is_empty()
+    {
+        let start = &proof.public_inputs[Table::Cpu as usize]
+            .get(..NUM_STATE_COLS)
+            .ok_or_else(|| anyhow!("missing CPU public inputs"))?;
+        if BoundaryState::commitment_from_public_inputs(start)
+            != BoundaryState::initial(prog).commitment()
+        {
+            return Err(anyhow!(
+                "execution does not start in the initial state"
+            ));
+        }
+        // ... and run all the way to a `Halt`
+        if !CPUStark::<F, D>::ends_in_halt(
+            &proof.public_inputs[Table::Cpu as usize],
+        )? {
+            return Err(anyhow!("execution does not end in a `Halt`"));
+        }
+    }
+
+    verify_all(
+        &zkvm,
+        proof,
+        &known_program_columns::<F, D>(prog, Some(&prog.memory_init), None),
+    )?;
+    PixiePublicValues::from_public_inputs::<F, D>(&proof.public_inputs)
+}
+
+/// Machine state at the boundary between two chunks of an execution
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoundaryState {
+    pub clock: u32,
+    pub program_counter: u8,
+    pub registers: [Word; REGISTER_COUNT],
+}
+
+impl BoundaryState {
+    /// State every execution starts in
+    pub fn initial(prog: &Program) -> Self {
+        Self {
+            clock: 1,
+            program_counter: prog.entry_point,
+            registers: [0; REGISTER_COUNT],
+        }
+    }
+
+    pub fn from_row(row: &SimulationRow) -> Self {
+        Self {
+            clock: row.clock,
+            program_counter: row.program_counter,
+            registers: row.registers,
+        }
+    }
+
+    /// Commitment to a boundary as found in the `CPUStark` public inputs,
+    /// i.e. `Clk`, `PC` and the [REGISTER_COUNT] registers as field
+    /// elements, see [Self::commitment]
+    pub fn commitment_from_public_inputs<F: RichField>(
+        state: &[F]
+    ) -> HashOut<F> {
+        PoseidonHash::hash_no_pad(state)
+    }
+
+    pub fn commitment<F: RichField>(&self) -> HashOut<F> {
+        let mut state = vec![
+            F::from_canonical_u32(self.clock),
+            F::from_canonical_u8(self.program_counter),
+        ];
+        state.extend(
+            self.registers
+                .iter()
+                .map(|&reg| F::from_canonical_u32(reg)),
+        );
+        Self::commitment_from_public_inputs(&state)
+    }
+}
+
+/// Proof of an execution split into chunks of CPU rows. Consecutive
+/// chunks overlap by one row: the last row of a chunk is the first row
+/// of the next one, which is the continuation argument tying them
+/// together.
+///
+/// The memory is handed over at the boundaries just as well: the program
+/// table of each chunk holds the memory its memory table starts from and
+/// the memory it ends with, see [ctl_memory_program_final], and the
+/// verifier checks both against [ChunkedProof::boundary_memories]. The
+/// memory a chunk ends with already has the shared row executed, which
+/// the next chunk executing it again leaves as is.
+pub struct ChunkedProof<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub chunk_proofs: Vec<PixieProof<F, C, D>>,
+    /// Commitments to the states at the start of the execution, at each
+    /// chunk boundary and at the end of the execution, i.e. one more
+    /// than there are chunks
+    pub boundary_commitments: Vec<HashOut<F>>,
+    /// Memory at the same points as [Self::boundary_commitments], starting
+    /// with the initial memory of the program
+    pub boundary_memories: Vec<HashMap<Word, Word>>,
+}
+
+/// Proves `prog` in chunks of `chunk_rows` executed rows each, see
+/// [ChunkedProof]
+pub fn generate_chunked_proof<F, C, const D: usize>(
+    prog: &Program,
+    chunk_rows: usize,
+) -> Result<ChunkedProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    if chunk_rows == 0 {
+        return Err(anyhow!("chunks need to have at least one row"));
+    }
+
+    let zkvm = PixieZKVM::<F, D>::new();
+
+    let simulation = PreflightSimulation::simulate(prog)?;
+    let rows = &simulation.trace_rows;
+    if rows.is_empty() {
+        return Err(anyhow!("program without code has nothing to chunk"));
+    }
+
+    let mut chunk_proofs = vec![];
+    let mut boundary_commitments =
+        vec![BoundaryState::from_row(&rows[0]).commitment()];
+    let mut boundary_memories = vec![simulation
+        .memory_init
+        .clone()];
+    let mut start = 0;
+    loop {
+        let end = (start + chunk_rows).min(rows.len() - 1);
+        let (chunk_proof, memory_final) = prove_rows::<F, C, D>(
+            &zkvm,
+            prog,
+            &boundary_memories[boundary_memories.len() - 1],
+            &rows[start..=end],
+        )?;
+        chunk_proofs.push(chunk_proof);
+        boundary_commitments
+            .push(BoundaryState::from_row(&rows[end]).commitment());
+        boundary_memories.push(memory_final);
+
+        if end == rows.len() - 1 {
+            break;
+        }
+        start = end;
+    }
+
+    Ok(ChunkedProof {
+        chunk_proofs,
+        boundary_commitments,
+        boundary_memories,
+    })
+}
+
+/// Proves the contiguous run of `rows` on its own, its memory table
+/// starting from `memory_init`. Returns the proof along with the memory
+/// the run ends with, see [MemoryStark::final_memory].
+fn prove_rows<F, C, const D: usize>(
+    zkvm: &PixieZKVM<F, D>,
+    prog: &Program,
+    memory_init: &HashMap<Word, Word>,
+    rows: &[SimulationRow],
+) -> Result<(PixieProof<F, C, D>, HashMap<Word, Word>)>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let proof = prove_all::<F, C, D>(
+        zkvm,
+        StarkSet::<F>::from_rows::<D>(prog, memory_init, rows),
+    )?;
+    Ok((proof, MemoryStark::<F, D>::final_memory(memory_init, rows)))
+}
+
+/// Verifies every chunk of `proof` and that the chunks chain from the
+/// initial state and memory of `prog` through the committed boundaries
+pub fn verify_chunked_proof<F, C, const D: usize>(
+    proof: &ChunkedProof<F, C, D>,
+    prog: &Program,
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let zkvm = PixieZKVM::<F, D>::new();
+
+    if proof
+        .boundary_commitments
+        .len()
+        != proof
+            .chunk_proofs
+            .len()
+            + 1
+        || proof
+            .boundary_memories
+            .len()
+            != proof
+                .boundary_commitments
+                .len()
+    {
+        return Err(anyhow!("expected one boundary more than chunks"));
+    }
+    if proof.boundary_commitments[0]
+        != BoundaryState::initial(prog).commitment()
+    {
+        return Err(anyhow!("execution does not start in the initial state"));
+    }
+    if proof.boundary_memories[0] != prog.memory_init {
+        return Err(anyhow!(
+            "execution does not start from the initial memory"
+        ));
+    }
+
+    for (idx, chunk_proof) in proof
+        .chunk_proofs
+        .iter()
+        .enumerate()
+    {
+        let (start, end) = CPUStark::<F, D>::boundaries(
+            &chunk_proof.public_inputs[Table::Cpu as usize],
+        );
+        if BoundaryState::commitment_from_public_inputs(start)
+            != proof.boundary_commitments[idx]
+            || BoundaryState::commitment_from_public_inputs(end)
+                != proof.boundary_commitments[idx + 1]
+        {
+            return Err(anyhow!("chunk {} does not match its boundaries", idx));
+        }
+        verify_all(
+            &zkvm,
+            chunk_proof,
+            &known_program_columns::<F, D>(
+                prog,
+                Some(&proof.boundary_memories[idx]),
+                Some(&proof.boundary_memories[idx + 1]),
+            ),
+        )?;
+    }
+
+    let ends_in_halt = match proof
+        .chunk_proofs
+        .last()
+    {
+        Some(last) => CPUStark::<F, D>::ends_in_halt(
+            &last.public_inputs[Table::Cpu as usize],
+        )?,
+        None => false,
+    };
+    if !ends_in_halt {
+        return Err(anyhow!("execution does not end in a `Halt`"));
+    }
 
     Ok(())
 }
+
+/// Proof of the cycles `[start, end)` of an execution on its own. Windows
+/// do not depend on each other, so they can be proven in parallel and
+/// verified independently.
+///
+/// Like the chunks of a [ChunkedProof], a window also covers the row at
+/// `clk = end`: the CPU public inputs hold the state the window starts in
+/// and the state its last cycle leaves behind, which is the state the
+/// window `[end, ..)` starts in. The memory is handed over the same way
+/// as between chunks: the memory table of a window starts from
+/// [WindowProof::memory_start] and ends with [WindowProof::memory_end],
+/// which is the `memory_start` of the window `[end, ..)`.
+pub struct WindowProof<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub start: u32,
+    pub end: u32,
+    pub proof: PixieProof<F, C, D>,
+    /// Memory the window starts from: the initial memory of the program
+    /// for the first window, the memory the row at `clk = start` leaves
+    /// behind otherwise
+    pub memory_start: HashMap<Word, Word>,
+    /// Memory the row at `clk = end` leaves behind
+    pub memory_end: HashMap<Word, Word>,
+}
+
+impl<F, C, const D: usize> WindowProof<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    /// `(Clk, PC, Reg R0, ..., Reg Rn)` at the start and at the end of the
+    /// window, as found in the `CPUStark` public inputs
+    pub fn boundaries(&self) -> (&[F], &[F]) {
+        CPUStark::<F, D>::boundaries(
+            &self
+                .proof
+                .public_inputs[Table::Cpu as usize],
+        )
+    }
+}
+
+/// Proves the cycles `[start, end)` of the execution `sim` of `prog`, see
+/// [WindowProof]. Both `start` and `end` have to be clocks of rows of
+/// `sim`.
+pub fn generate_window_proof<F, C, const D: usize>(
+    prog: &Program,
+    sim: &PreflightSimulation,
+    start: u32,
+    end: u32,
+) -> Result<WindowProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    if start >= end {
+        return Err(anyhow!("window [{}, {}) has no cycles", start, end));
+    }
+    let row_at = |clock: u32| {
+        sim.trace_rows
+            .iter()
+            .position(|row| row.clock == clock)
+            .ok_or_else(|| anyhow!("execution has no row at clk = {}", clock))
+    };
+    let (start_idx, end_idx) = (row_at(start)?, row_at(end)?);
+    let memory_start = match start_idx {
+        0 => sim
+            .memory_init
+            .clone(),
+        _ => MemoryStark::<F, D>::final_memory(
+            &sim.memory_init,
+            &sim.trace_rows[..=start_idx],
+        ),
+    };
+
+    let zkvm = PixieZKVM::<F, D>::new();
+    let (proof, memory_end) = prove_rows::<F, C, D>(
+        &zkvm,
+        prog,
+        &memory_start,
+        &sim.trace_rows[start_idx..=end_idx],
+    )?;
+    Ok(WindowProof {
+        start,
+        end,
+        proof,
+        memory_start,
+        memory_end,
+    })
+}
+
+/// Verifies `proof` to be a proof of the cycles `[proof.start, proof.end)`
+/// of an execution of `prog`, running from [WindowProof::memory_start] to
+/// [WindowProof::memory_end]. A window starting at `clk = 1` has to start
+/// in the initial state and from the initial memory of `prog`.
+pub fn verify_window_proof<F, C, const D: usize>(
+    proof: &WindowProof<F, C, D>,
+    prog: &Program,
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let zkvm = PixieZKVM::<F, D>::new();
+
+    let (start, end) = proof.boundaries();
+    if start.first() != Some(&F::from_canonical_u32(proof.start))
+        || end.first() != Some(&F::from_canonical_u32(proof.end))
+    {
+        return Err(anyhow!(
+            "window does not cover [{}, {})",
+            proof.start,
+            proof.end
+        ));
+    }
+    if proof.start == 1
+        && BoundaryState::commitment_from_public_inputs(start)
+            != BoundaryState::initial(prog).commitment()
+    {
+        return Err(anyhow!("execution does not start in the initial state"));
+    }
+    if proof.start == 1 && proof.memory_start != prog.memory_init {
+        return Err(anyhow!(
+            "execution does not start from the initial memory"
+        ));
+    }
+
+    verify_all(
+        &zkvm,
+        &proof.proof,
+        &known_program_columns::<F, D>(
+            prog,
+            Some(&proof.memory_start),
+            Some(&proof.memory_end),
+        ),
+    )
+}
+
+/// Proves and verifies `traces` with all the cross-table lookups of the
+/// VM, panicking on any failure. The program table is not checked against
+/// any program, see [verify_with_ctls].
+#[cfg(test)]
+pub(crate) fn prove_and_verify_traces<F, C, const D: usize>(
+    traces: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_inputs: &[Vec<F>; NUM_TABLES],
+) where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    let zkvm = PixieZKVM::<F, D>::new();
+    let proof = prove_all::<F, C, D>(
+        &zkvm,
+        StarkSet {
+            traces,
+            public_inputs: public_inputs.clone(),
+        },
+    )
+    .unwrap();
+    verify_all(&zkvm, &proof, &[]).unwrap();
+}
+
+/// Simulates `program` and returns the simulation along with the traces
+/// of all the tables, panicking if the simulation fails
+#[cfg(test)]
+pub(crate) fn simulate_and_trace<F, const D: usize>(
+    program: &Program
+) -> (PreflightSimulation, [Vec<PolynomialValues<F>>; NUM_TABLES])
+where
+    F: RichField + Extendable<D>,
+{
+    let simulation = PreflightSimulation::simulate(program).unwrap();
+    let traces = generate_traces::<F, D>(program, &simulation);
+    (simulation, traces)
+}
+
+/// Proves and verifies `traces` against the public inputs of
+/// `simulation`, see [prove_and_verify_traces]
+#[cfg(test)]
+pub(crate) fn prove_and_verify<F, C, const D: usize>(
+    simulation: &PreflightSimulation,
+    traces: [Vec<PolynomialValues<F>>; NUM_TABLES],
+) where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>,
+{
+    prove_and_verify_traces::<F, C, D>(
+        traces,
+        &generate_public_inputs::<F, D>(simulation),
+    );
+}
@@ -3,8 +3,16 @@
 //! a.k.a. resting code is known prior to proof generation. This
 //! needs to be differentiated from actual running process trace, since
 //! that may be longer than "program" owing to actual execution of jumps.
+//! Next to the code it holds the memory the execution starts from and the
+//! memory it ends with, both of which the verifier knows just as well. The
+//! verifier checks all of these columns, see [KNOWN_COLS].
+
+use core::{
+    marker::PhantomData,
+    mem::offset_of,
+};
+use std::collections::HashMap;
 
-use core::marker::PhantomData;
 use plonky2::{
     field::{
         extension::{
@@ -13,6 +21,7 @@ use plonky2::{
         },
         packed::PackedField,
         polynomial::PolynomialValues,
+        types::Field,
     },
     hash::hash_types::RichField,
     iop::ext_target::ExtensionTarget,
@@ -27,21 +36,183 @@ use starky::{
         StarkEvaluationFrame,
         StarkFrame,
     },
+    lookup::{
+        Column,
+        Filter,
+    },
     stark::Stark,
     util::trace_rows_to_poly_values,
 };
 
-use crate::vm_specs::Program;
+use crate::{
+    preflight_simulator::SimulationRow,
+    utilities::debug_table,
+    utility_macros::derive_get_number_of_columns,
+    vm_specs::{
+        Instruction,
+        Program,
+        Word,
+        INSTRUCTION_DATA_WEIGHTS,
+    },
+};
 
 // Table description:
-// +-----------------+--------------------+-------------+
-// | Program Counter | Instruction Opcode | Is_Executed |
-// +-----------------+--------------------+-------------+
-// |    ....         |     ....           |    ....     |
-// |    ....         |     ....           |    ....     |
-// +-----------------+--------------------+-------------+
-const NUMBER_OF_COLS: usize = 3;
+// +-----------------+--------------------+-------------+-----------+-----------+--------------+-----------+------------+---------+------------+-------------+----------+
+// | Program Counter | Instruction Opcode | Is_Executed | Operand A | Operand B | Multiplicity | Init_Addr | Init_Value | Is_Init | Final_Addr | Final_Value | Is_Final |
+// +-----------------+--------------------+-------------+-----------+-----------+--------------+-----------+------------+---------+------------+-------------+----------+
+// |    ....         |     ....           |    ....     |   ....    |   ....    |    ....      |   ....    |    ....    |  ....   |   ....     |    ....     |   ....   |
+// |    ....         |     ....           |    ....     |   ....    |   ....    |    ....      |   ....    |    ....    |  ....   |   ....     |    ....     |   ....   |
+// +-----------------+--------------------+-------------+-----------+-----------+--------------+-----------+------------+---------+------------+-------------+----------+
+//
+// `Is_Executed` is `1` for actual instructions, `0` for padding.
+// `Multiplicity` is the number of times the instruction is executed by the
+// CPU, i.e. the number of times it is looked up from the CPU table.
+// `Init_Addr` and `Init_Value` hold the cells of the memory the execution
+// starts from, sorted by address, one per row where `Is_Init` is set. The
+// init rows of the memory table other than the implicit ones are looked
+// up here.
+// `Final_Addr` and `Final_Value` likewise hold the memory the execution
+// ends with, one cell per row where `Is_Final` is set. The last access of
+// every address of the memory table is looked up here, which pins down the
+// memory handed over to the next chunk of a chunked proof.
+
+/// Columns of the table, in order. Each field is one column, see
+/// [ProgramInstructions::get_number_of_columns].
+#[repr(C)]
+pub struct ProgramInstructions<T> {
+    pub pc: T,
+    pub opcode: T,
+    pub is_exec: T,
+    pub operand_a: T,
+    pub operand_b: T,
+    pub multiplicity: T,
+    pub init_addr: T,
+    pub init_value: T,
+    pub is_init: T,
+    pub final_addr: T,
+    pub final_value: T,
+    pub is_final: T,
+}
+derive_get_number_of_columns!(ProgramInstructions);
+
+const NUMBER_OF_COLS: usize =
+    ProgramInstructions::<u8>::get_number_of_columns();
 const PUBLIC_INPUTS: usize = 0;
+const ROW_HEADINGS: [&str; NUMBER_OF_COLS] = [
+    "pc",
+    "opcode",
+    "is_exec",
+    "operand_a",
+    "operand_b",
+    "multiplicity",
+    "init_addr",
+    "init_value",
+    "is_init",
+    "final_addr",
+    "final_value",
+    "is_final",
+];
+const COLUMN_DESCRIPTIONS: [&str; NUMBER_OF_COLS] = [
+    "Location of the instruction",
+    "Opcode of the instruction, see `Instruction::get_opcode`",
+    "Set on actual instructions, unset on padding",
+    "First operand of the instruction",
+    "Second operand of the instruction",
+    "Number of times the instruction is executed",
+    "Address of a cell of the initial memory",
+    "Value of the cell of the initial memory",
+    "Set on rows holding a cell of the initial memory",
+    "Address of a cell of the final memory",
+    "Value of the cell of the final memory",
+    "Set on rows holding a cell of the final memory",
+];
+
+// With `u8` cells, the offset of a field is the index of its column
+const COL_PC: usize = offset_of!(ProgramInstructions<u8>, pc);
+const COL_OPCODE: usize = offset_of!(ProgramInstructions<u8>, opcode);
+const COL_IS_EXEC: usize = offset_of!(ProgramInstructions<u8>, is_exec);
+const COL_OPERAND_A: usize = offset_of!(ProgramInstructions<u8>, operand_a);
+const COL_OPERAND_B: usize = offset_of!(ProgramInstructions<u8>, operand_b);
+const COL_MULTIPLICITY: usize =
+    offset_of!(ProgramInstructions<u8>, multiplicity);
+const COL_INIT_ADDR: usize = offset_of!(ProgramInstructions<u8>, init_addr);
+const COL_INIT_VALUE: usize = offset_of!(ProgramInstructions<u8>, init_value);
+const COL_IS_INIT: usize = offset_of!(ProgramInstructions<u8>, is_init);
+const COL_FINAL_ADDR: usize = offset_of!(ProgramInstructions<u8>, final_addr);
+const COL_FINAL_VALUE: usize =
+    offset_of!(ProgramInstructions<u8>, final_value);
+const COL_IS_FINAL: usize = offset_of!(ProgramInstructions<u8>, is_final);
+
+/// Columns whose values do not depend on the execution, which a verifier
+/// rebuilds from the program and checks against the proof, see
+/// `stark_pixie_zkvm::verify_with_ctls`. All but `Multiplicity`.
+pub const KNOWN_COLS: [usize; NUMBER_OF_COLS - 1] = [
+    COL_PC,
+    COL_OPCODE,
+    COL_IS_EXEC,
+    COL_OPERAND_A,
+    COL_OPERAND_B,
+    COL_INIT_ADDR,
+    COL_INIT_VALUE,
+    COL_IS_INIT,
+    COL_FINAL_ADDR,
+    COL_FINAL_VALUE,
+    COL_IS_FINAL,
+];
+
+/// Columns of [KNOWN_COLS] holding the initial memory
+pub const MEMORY_INIT_COLS: [usize; 3] =
+    [COL_INIT_ADDR, COL_INIT_VALUE, COL_IS_INIT];
+
+/// Columns of [KNOWN_COLS] holding the final memory
+pub const MEMORY_FINAL_COLS: [usize; 3] =
+    [COL_FINAL_ADDR, COL_FINAL_VALUE, COL_IS_FINAL];
+
+/// Filter for the Program side of the CPU <-> Program cross-table lookup.
+/// Each instruction is looked up as many times as it is executed.
+pub fn ctl_filter_cpu<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::single(COL_MULTIPLICITY))
+}
+
+/// `instruction_data`, see `Instruction::instruction_data`, looked up from
+/// the CPU table
+pub fn ctl_data_cpu<F: Field>() -> Vec<Column<F>> {
+    vec![Column::linear_combination(
+        [COL_PC, COL_OPCODE, COL_OPERAND_A, COL_OPERAND_B]
+            .into_iter()
+            .zip(INSTRUCTION_DATA_WEIGHTS.map(F::from_canonical_u64)),
+    )]
+}
+
+/// Location of the instruction, looked up from the CPU table. Filtered
+/// like [ctl_filter_cpu], as every execution looks up its `PC` once.
+pub fn ctl_data_program_counter<F: Field>() -> Vec<Column<F>> {
+    vec![Column::single(COL_PC)]
+}
+
+/// Filter for the Program side of the Memory <-> Program cross-table
+/// lookup of the initial memory. Each cell is looked up exactly once.
+pub fn ctl_filter_memory_init<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::single(COL_IS_INIT))
+}
+
+/// `(Init_Addr, Init_Value)` looked up from the init rows of the memory
+/// table
+pub fn ctl_data_memory_init<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_INIT_ADDR, COL_INIT_VALUE]).collect()
+}
+
+/// Filter for the Program side of the Memory <-> Program cross-table
+/// lookup of the final memory. Each cell is looked up exactly once.
+pub fn ctl_filter_memory_final<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::single(COL_IS_FINAL))
+}
+
+/// `(Final_Addr, Final_Value)` looked up from the last access of every
+/// address of the memory table
+pub fn ctl_data_memory_final<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_FINAL_ADDR, COL_FINAL_VALUE]).collect()
+}
 
 #[derive(Clone, Copy)]
 pub struct ProgramInstructionsStark<F, const D: usize> {
@@ -56,14 +227,46 @@ where
         Self { _f: PhantomData }
     }
 
-    pub fn generate_trace(prog: &Program) -> Vec<PolynomialValues<F>>
+    /// Heading and a short description of every column of the table
+    pub fn column_docs() -> Vec<(&'static str, &'static str)> {
+        ROW_HEADINGS
+            .into_iter()
+            .zip(COLUMN_DESCRIPTIONS)
+            .collect()
+    }
+
+    /// Trace of the program, one row per instruction sorted by `PC`, with
+    /// the multiplicities of each instruction as seen in the executed `rows`.
+    /// The cells of `memory_init` and `memory_final` go alongside, each
+    /// sorted by address.
+    pub fn generate_trace(
+        prog: &Program,
+        memory_init: &HashMap<Word, Word>,
+        memory_final: &HashMap<Word, Word>,
+        rows: &[SimulationRow],
+    ) -> Vec<PolynomialValues<F>>
     where
         F: RichField,
     {
-        let mut trace = prog
+        let mut multiplicities: HashMap<u8, u64> = HashMap::new();
+        for row in rows {
+            *multiplicities
+                .entry(row.program_counter)
+                .or_default() += 1;
+        }
+
+        // `HashMap` iteration order is arbitrary, sorting keeps the trace
+        // the same from one run to the next
+        let mut code = prog
             .code
             .iter()
+            .collect::<Vec<(&u8, &Instruction)>>();
+        code.sort_by_key(|&(pc, _)| *pc);
+
+        let mut trace = code
+            .into_iter()
             .map(|(pc, inst)| {
+                let [operand_a, operand_b] = inst.get_operands();
                 [
                     // Program Counter (ID = 0)
                     F::from_canonical_u8(*pc),
@@ -71,15 +274,56 @@ where
                     F::from_canonical_u8(inst.get_opcode()),
                     // Filter, true if actual instructions (ID = 2)
                     F::ONE,
+                    // Operands (ID = 3, 4)
+                    F::from_canonical_u32(operand_a),
+                    F::from_canonical_u32(operand_b),
+                    // Multiplicity (ID = 5)
+                    F::from_canonical_u64(
+                        multiplicities
+                            .get(pc)
+                            .copied()
+                            .unwrap_or_default(),
+                    ),
+                    // Initial memory (ID = 6, 7, 8), filled below
+                    F::ZERO,
+                    F::ZERO,
+                    F::ZERO,
+                    // Final memory (ID = 9, 10, 11), filled below
+                    F::ZERO,
+                    F::ZERO,
+                    F::ZERO,
                 ]
             })
             .collect::<Vec<[F; NUMBER_OF_COLS]>>();
 
+        for (memory, [col_addr, col_value, col_filter]) in [
+            (memory_init, MEMORY_INIT_COLS),
+            (memory_final, MEMORY_FINAL_COLS),
+        ] {
+            let mut cells = memory
+                .iter()
+                .collect::<Vec<(&Word, &Word)>>();
+            cells.sort_by_key(|&(addr, _)| *addr);
+            if trace.len() < cells.len() {
+                trace.resize(cells.len(), [F::ZERO; NUMBER_OF_COLS]);
+            }
+            for (row, (&addr, &value)) in trace
+                .iter_mut()
+                .zip(cells)
+            {
+                row[col_addr] = F::from_canonical_u32(addr);
+                row[col_value] = F::from_canonical_u32(value);
+                row[col_filter] = F::ONE;
+            }
+        }
+
+        debug_table("program", ROW_HEADINGS, &trace);
+
         // Need to pad the trace to a len of some power of 2
         let pow2_len = trace
             .len()
             .next_power_of_two();
-        trace.resize(pow2_len, [F::ZERO, F::ZERO, F::ZERO]);
+        trace.resize(pow2_len, [F::ZERO; NUMBER_OF_COLS]);
 
         // Convert into polynomial values
         trace_rows_to_poly_values(trace)
@@ -115,70 +359,320 @@ where
         let local_values = vars.get_local_values();
 
         // Check if filter column is either 0 or 1
-        let filter_column = local_values[2];
+        let filter_column = local_values[COL_IS_EXEC];
         yield_constr.constraint(filter_column * (P::ONES - filter_column));
+
+        // Padding rows are never looked up
+        yield_constr.constraint(
+            local_values[COL_MULTIPLICITY] * (P::ONES - filter_column),
+        );
     }
 
     fn eval_ext_circuit(
         &self,
-        _builder: &mut CircuitBuilder<F, D>,
-        _vars: &Self::EvaluationFrameTarget,
-        _yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: &Self::EvaluationFrameTarget,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
-        unimplemented!()
+        let local_values = vars.get_local_values();
+        let one = builder.one_extension();
+
+        // Check if filter column is either 0 or 1
+        let filter_column = local_values[COL_IS_EXEC];
+        let not_filter = builder.sub_extension(one, filter_column);
+        let constraint = builder.mul_extension(filter_column, not_filter);
+        yield_constr.constraint(builder, constraint);
+
+        // Padding rows are never looked up
+        let constraint =
+            builder.mul_extension(local_values[COL_MULTIPLICITY], not_filter);
+        yield_constr.constraint(builder, constraint);
     }
 
     fn constraint_degree(&self) -> usize {
         3
     }
+
+    fn requires_ctls(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use plonky2::plonk::config::{
+        GenericConfig,
+        PoseidonGoldilocksConfig,
+    };
+
+    use starky::stark_testing::test_stark_circuit_constraints;
 
-    use plonky2::{
-        field::goldilocks_field::GoldilocksField,
-        plonk::config::{
-            GenericConfig,
-            PoseidonGoldilocksConfig,
+    use crate::{
+        preflight_simulator::PreflightSimulation,
+        stark_memory::MemoryStark,
+        stark_pixie_zkvm::{
+            generate_public_inputs,
+            generate_traces,
+            known_program_columns,
+            prove_all,
+            prove_and_verify_traces,
+            verify_all,
+            PixieZKVM,
+            StarkSet,
+            Table,
+        },
+        vm_specs::{
+            Instruction,
+            InstructionLocation,
+            MemoryLocation,
+            ProgramBuilder,
+            Register,
+            INSTRUCTION_ENCODING_WEIGHTS,
         },
-        util::timing::TimingTree,
-    };
-    use starky::{
-        config::StarkConfig,
-        proof::StarkProofWithPublicInputs,
-        prover::prove,
-        verifier::verify_stark_proof,
     };
 
     use super::*;
 
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
     #[test]
     fn test_nil_program() {
-        const D: usize = 2;
-        type C = PoseidonGoldilocksConfig;
-        type F = <C as GenericConfig<D>>::F;
-        type S = ProgramInstructionsStark<F, D>;
-        type PR = StarkProofWithPublicInputs<GoldilocksField, C, 2>;
-
-        let stark = S::new();
-        let mut config = StarkConfig::standard_fast_config();
-        // Need to do this since our table is small. Need atleast 1<<5
-        // sized table to not affect this
-        config
-            .fri_config
-            .cap_height = 1;
         let program = Program::default();
-        let trace = ProgramInstructionsStark::<F, D>::generate_trace(&program);
-        let proof: Result<PR, anyhow::Error> = prove(
-            stark.clone(),
-            &config,
-            trace,
-            &[],
-            &mut TimingTree::default(),
-        );
-        assert!(proof.is_ok());
-        let verification = verify_stark_proof(stark, proof.unwrap(), &config);
-        assert!(verification.is_ok());
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        prove_and_verify_traces::<F, C, D>(
+            generate_traces::<F, D>(&program, &simulation),
+            &generate_public_inputs::<F, D>(&simulation),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    /// The CPU claims to execute an instruction which is not part of
+    /// `Program::code`, so the CPU <-> Program lookup can not link up
+    fn test_instruction_absent_from_program() {
+        let program = crate::fixtures::add();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+
+        // Same execution, but the `Add` at `pc = 2` was a `Jnz` in the
+        // committed program
+        let mut tampered_program = program.clone();
+        tampered_program
+            .code
+            .insert(
+                2,
+                Instruction::Jnz(Register::R0, InstructionLocation(0x02)),
+            );
+        let mut traces = generate_traces::<F, D>(&program, &simulation);
+        traces[Table::ProgramInstructions as usize] =
+            ProgramInstructionsStark::<F, D>::generate_trace(
+                &tampered_program,
+                &tampered_program.memory_init,
+                &MemoryStark::<F, D>::final_memory(
+                    &tampered_program.memory_init,
+                    &simulation.trace_rows,
+                ),
+                &simulation.trace_rows,
+            );
+
+        prove_and_verify_traces::<F, C, D>(
+            traces,
+            &generate_public_inputs::<F, D>(&simulation),
+        );
+    }
+
+    #[test]
+    /// The table of a program with one more instruction, never executed,
+    /// links up with the CPU table all the same. Only checking the table
+    /// against the program tells the two apart.
+    fn test_program_table_of_other_program() {
+        let program = crate::fixtures::add();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+
+        let mut other_program = program.clone();
+        other_program
+            .code
+            .insert(5, Instruction::Halt);
+        let mut tables =
+            StarkSet::<F>::from_simulation::<D>(&program, &simulation);
+        tables.traces[Table::ProgramInstructions as usize] =
+            ProgramInstructionsStark::<F, D>::generate_trace(
+                &other_program,
+                &other_program.memory_init,
+                &MemoryStark::<F, D>::final_memory(
+                    &other_program.memory_init,
+                    &simulation.trace_rows,
+                ),
+                &simulation.trace_rows,
+            );
+
+        let zkvm = PixieZKVM::<F, D>::new();
+        let proof = prove_all::<F, C, D>(&zkvm, tables).unwrap();
+        let known_columns = |prog: &Program| {
+            known_program_columns::<F, D>(prog, Some(&prog.memory_init), None)
+        };
+        assert!(
+            verify_all(&zkvm, &proof, &known_columns(&other_program)).is_ok()
+        );
+        assert!(verify_all(&zkvm, &proof, &known_columns(&program)).is_err());
+    }
+
+    #[test]
+    /// One row per instruction in `PC` order, padded with unexecuted rows
+    /// to a power of two
+    fn test_generate_trace() {
+        let program = crate::fixtures::simple_loop();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let trace = ProgramInstructionsStark::<F, D>::generate_trace(
+            &program,
+            &program.memory_init,
+            &MemoryStark::<F, D>::final_memory(
+                &program.memory_init,
+                &simulation.trace_rows,
+            ),
+            &simulation.trace_rows,
+        );
+        assert_eq!(
+            trace[COL_PC].len(),
+            program
+                .code
+                .len()
+                .next_power_of_two()
+        );
+
+        let code_len = program
+            .code
+            .len();
+        for (row, pc) in (0..code_len).zip(0u8..) {
+            assert_eq!(trace[COL_PC].values[row], F::from_canonical_u8(pc));
+            assert_eq!(trace[COL_IS_EXEC].values[row], F::ONE);
+        }
+        for row in code_len..trace[COL_PC].len() {
+            assert_eq!(trace[COL_IS_EXEC].values[row], F::ZERO);
+            assert_eq!(trace[COL_MULTIPLICITY].values[row], F::ZERO);
+        }
+    }
+
+    #[test]
+    /// The opcode and operand columns are the limbs of the canonical
+    /// encoding of each instruction, in `PC` order
+    fn test_encoded_instructions() {
+        let program = crate::fixtures::countdown(3);
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let trace = ProgramInstructionsStark::<F, D>::generate_trace(
+            &program,
+            &program.memory_init,
+            &MemoryStark::<F, D>::final_memory(
+                &program.memory_init,
+                &simulation.trace_rows,
+            ),
+            &simulation.trace_rows,
+        );
+
+        let code_len = program
+            .code
+            .len();
+        for (row, pc) in (0..code_len).zip(0u8..) {
+            let encoding = [COL_OPCODE, COL_OPERAND_A, COL_OPERAND_B]
+                .into_iter()
+                .zip(INSTRUCTION_ENCODING_WEIGHTS)
+                .map(|(col, weight)| {
+                    trace[col].values[row] * F::from_canonical_u64(weight)
+                })
+                .sum::<F>();
+            assert_eq!(encoding, program.code[&pc].encode_to_field::<F>());
+            assert_eq!(
+                Instruction::decode_from_field(encoding).unwrap(),
+                program.code[&pc]
+            );
+        }
+    }
+
+    #[test]
+    /// Operands are part of the lookup, not just the opcode
+    fn test_instruction_data_differs_by_operand() {
+        let lb_40 = Instruction::Lb(Register::R0, MemoryLocation(0x40));
+        let lb_41 = Instruction::Lb(Register::R0, MemoryLocation(0x41));
+        assert_ne!(
+            lb_40.instruction_data::<F>(0),
+            lb_41.instruction_data::<F>(0)
+        );
+        assert_ne!(
+            lb_40.instruction_data::<F>(0),
+            lb_40.instruction_data::<F>(1)
+        );
+    }
+
+    #[test]
+    fn test_circuit_constraints() {
+        let stark = ProgramInstructionsStark::<F, D>::new();
+        assert!(test_stark_circuit_constraints::<F, C, _, D>(stark).is_ok());
+    }
+
+    #[test]
+    /// The table is as wide as its column struct
+    fn test_number_of_columns() {
+        assert_eq!(
+            ProgramInstructions::<u8>::get_number_of_columns(),
+            NUMBER_OF_COLS
+        );
+        assert_eq!(ProgramInstructionsStark::<F, D>::COLUMNS, NUMBER_OF_COLS);
+        assert!(!KNOWN_COLS.contains(&COL_MULTIPLICITY));
+    }
+
+    #[test]
+    /// The cells of the initial and final memory sit next to the code,
+    /// sorted by address
+    fn test_memory_columns() {
+        let program = ProgramBuilder::new()
+            .memory(0x41, 0x45)
+            .memory(0x40, 0x20)
+            .memory(0x42, 0x00)
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        let trace = ProgramInstructionsStark::<F, D>::generate_trace(
+            &program,
+            &program.memory_init,
+            &MemoryStark::<F, D>::final_memory(
+                &program.memory_init,
+                &simulation.trace_rows,
+            ),
+            &simulation.trace_rows,
+        );
+        assert_eq!(trace[COL_PC].len(), 4);
+        assert_eq!(
+            trace[COL_IS_EXEC].values,
+            [1, 0, 0, 0].map(F::from_canonical_u8)
+        );
+        assert_eq!(
+            trace[COL_INIT_ADDR].values,
+            [0x40, 0x41, 0x42, 0].map(F::from_canonical_u8)
+        );
+        assert_eq!(
+            trace[COL_INIT_VALUE].values,
+            [0x20, 0x45, 0, 0].map(F::from_canonical_u8)
+        );
+        assert_eq!(
+            trace[COL_IS_INIT].values,
+            [1, 1, 1, 0].map(F::from_canonical_u8)
+        );
+        // Nothing is accessed, so the memory ends as it started
+        for (init_col, final_col) in MEMORY_INIT_COLS
+            .into_iter()
+            .zip(MEMORY_FINAL_COLS)
+        {
+            assert_eq!(trace[init_col].values, trace[final_col].values);
+        }
+    }
+
+    #[test]
+    fn test_column_docs() {
+        assert_eq!(
+            ProgramInstructionsStark::<F, D>::column_docs().len(),
+            ProgramInstructionsStark::<F, D>::COLUMNS
+        );
     }
 }
@@ -0,0 +1,293 @@
+//! This file is an encoding of all the bytes, `0..=255`. It is "static"
+//! in the same sense as the program: its values are known prior to proof
+//! generation, only how often each of them is looked up is not. Other
+//! tables prove that a value fits in a byte by looking it up here, see
+//! `stark_pixie_zkvm::ctl_range_check_u8`.
+
+use core::marker::PhantomData;
+
+use plonky2::{
+    field::{
+        extension::{
+            Extendable,
+            FieldExtension,
+        },
+        packed::PackedField,
+        polynomial::PolynomialValues,
+        types::Field,
+    },
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+use starky::{
+    constraint_consumer::{
+        ConstraintConsumer,
+        RecursiveConstraintConsumer,
+    },
+    evaluation_frame::{
+        StarkEvaluationFrame,
+        StarkFrame,
+    },
+    lookup::{
+        Column,
+        Filter,
+    },
+    stark::Stark,
+    util::trace_rows_to_poly_values,
+};
+
+use crate::vm_specs::{
+    Word,
+    WORD_BYTES,
+};
+
+// Table description:
+// +-------+--------------+
+// | Value | Multiplicity |
+// +-------+--------------+
+// |   0   |    ....      |
+// |  ...  |    ....      |
+// |  255  |    ....      |
+// +-------+--------------+
+//
+// `Value` runs over all bytes in order, one per row. The table has
+// exactly 256 rows, a power of two, so it is never padded.
+// `Multiplicity` is the number of times the value is looked up from the
+// other tables.
+const NUMBER_OF_COLS: usize = 2;
+const PUBLIC_INPUTS: usize = 0;
+const ROW_HEADINGS: [&str; NUMBER_OF_COLS] = ["value", "multiplicity"];
+const COLUMN_DESCRIPTIONS: [&str; NUMBER_OF_COLS] = [
+    "Byte, one row per value in `0..=255`",
+    "Number of times the byte is looked up",
+];
+
+const COL_VALUE: usize = 0;
+const COL_MULTIPLICITY: usize = 1;
+
+const NUMBER_OF_ROWS: usize = 1 << u8::BITS;
+
+/// Filter for the looked side of the range check lookup. Each byte is
+/// looked up as many times as it is used.
+pub fn ctl_filter<F: Field>() -> Filter<F> {
+    Filter::new_simple(Column::single(COL_MULTIPLICITY))
+}
+
+/// The byte, looked up from the other tables one column at a time
+pub fn ctl_data<F: Field>() -> Vec<Column<F>> {
+    vec![Column::single(COL_VALUE)]
+}
+
+/// Little endian bytes of `value` as field elements, as laid out in the
+/// byte columns of the tables looking up in this one
+pub fn word_bytes<F: Field>(value: Word) -> [F; WORD_BYTES] {
+    value
+        .to_le_bytes()
+        .map(F::from_canonical_u8)
+}
+
+/// Weight of each of the [word_bytes] in the value they decompose
+pub fn byte_weights<F: Field>() -> [F; WORD_BYTES] {
+    core::array::from_fn(|idx| {
+        F::from_canonical_u64(1 << (u8::BITS as usize * idx))
+    })
+}
+
+#[derive(Clone, Copy)]
+pub struct RangeCheckU8Stark<F, const D: usize> {
+    pub _f: PhantomData<F>,
+}
+
+impl<F, const D: usize> RangeCheckU8Stark<F, D>
+where
+    F: RichField + Extendable<D>,
+{
+    pub fn new() -> Self {
+        Self { _f: PhantomData }
+    }
+
+    /// Heading and a short description of every column of the table
+    pub fn column_docs() -> Vec<(&'static str, &'static str)> {
+        ROW_HEADINGS
+            .into_iter()
+            .zip(COLUMN_DESCRIPTIONS)
+            .collect()
+    }
+
+    /// Trace of all the bytes, with the multiplicities of each of them as
+    /// seen in `values`. Values which are not a byte are not counted, so
+    /// their lookups fail.
+    pub fn generate_trace(
+        values: impl IntoIterator<Item = F>
+    ) -> Vec<PolynomialValues<F>>
+    where
+        F: RichField,
+    {
+        let mut multiplicities = [0u64; NUMBER_OF_ROWS];
+        for value in values {
+            if let Some(multiplicity) =
+                multiplicities.get_mut(value.to_canonical_u64() as usize)
+            {
+                *multiplicity += 1;
+            }
+        }
+
+        let trace = multiplicities
+            .into_iter()
+            .enumerate()
+            .map(|(value, multiplicity)| {
+                [
+                    // Value (ID = 0)
+                    F::from_canonical_usize(value),
+                    // Multiplicity (ID = 1)
+                    F::from_canonical_u64(multiplicity),
+                ]
+            })
+            .collect::<Vec<[F; NUMBER_OF_COLS]>>();
+
+        // Convert into polynomial values
+        trace_rows_to_poly_values(trace)
+    }
+}
+
+impl<F, const D: usize> Stark<F, D> for RangeCheckU8Stark<F, D>
+where
+    F: RichField + Extendable<D>,
+{
+    type EvaluationFrame<FE, P, const D2: usize> = StarkFrame<P, P::Scalar, NUMBER_OF_COLS, PUBLIC_INPUTS>
+    where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>;
+    type EvaluationFrameTarget = StarkFrame<
+        ExtensionTarget<D>,
+        ExtensionTarget<D>,
+        NUMBER_OF_COLS,
+        PUBLIC_INPUTS,
+    >;
+
+    const COLUMNS: usize = NUMBER_OF_COLS;
+    const PUBLIC_INPUTS: usize = PUBLIC_INPUTS;
+
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: &Self::EvaluationFrame<FE, P, D2>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let local_values = vars.get_local_values();
+        let next_values = vars.get_next_values();
+
+        // `Value` starts at `0` and steps by one on every row. With
+        // exactly 256 rows, it ends at `255`.
+        let value = local_values[COL_VALUE];
+        yield_constr.constraint_first_row(value);
+        yield_constr
+            .constraint_transition(next_values[COL_VALUE] - value - P::ONES);
+        yield_constr
+            .constraint_last_row(value - FE::from_canonical_u8(u8::MAX));
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: &Self::EvaluationFrameTarget,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let local_values = vars.get_local_values();
+        let next_values = vars.get_next_values();
+        let one = builder.one_extension();
+
+        // `Value` starts at `0`, steps by one and ends at `255`
+        let value = local_values[COL_VALUE];
+        yield_constr.constraint_first_row(builder, value);
+        let value_diff = builder.sub_extension(next_values[COL_VALUE], value);
+        let constraint = builder.sub_extension(value_diff, one);
+        yield_constr.constraint_transition(builder, constraint);
+        let max = builder.constant_extension(F::Extension::from_canonical_u8(
+            u8::MAX,
+        ));
+        let constraint = builder.sub_extension(value, max);
+        yield_constr.constraint_last_row(builder, constraint);
+    }
+
+    fn constraint_degree(&self) -> usize {
+        3
+    }
+
+    fn requires_ctls(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::{
+        GenericConfig,
+        PoseidonGoldilocksConfig,
+    };
+
+    use starky::stark_testing::test_stark_circuit_constraints;
+
+    use super::*;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    /// Every byte gets one row, counting how often it is looked up
+    fn test_generate_trace() {
+        let values =
+            [0x00, 0x45, 0x45, 0xff, 0x100].map(F::from_canonical_u32);
+        let trace = RangeCheckU8Stark::<F, D>::generate_trace(values);
+
+        assert_eq!(trace[COL_VALUE].values.len(), NUMBER_OF_ROWS);
+        assert_eq!(trace[COL_VALUE].values[0x45], F::from_canonical_u8(0x45));
+        assert_eq!(trace[COL_MULTIPLICITY].values[0x00], F::ONE);
+        assert_eq!(
+            trace[COL_MULTIPLICITY].values[0x45],
+            F::from_canonical_u8(2)
+        );
+        assert_eq!(trace[COL_MULTIPLICITY].values[0xff], F::ONE);
+        // `0x100` is not a byte, and goes uncounted
+        assert_eq!(
+            trace[COL_MULTIPLICITY]
+                .values
+                .iter()
+                .copied()
+                .sum::<F>(),
+            F::from_canonical_u8(4)
+        );
+    }
+
+    #[test]
+    fn test_word_bytes() {
+        let bytes = word_bytes::<F>(0x1234_5678);
+        let recomposed = bytes
+            .into_iter()
+            .zip(byte_weights::<F>())
+            .map(|(byte, weight)| byte * weight)
+            .sum::<F>();
+        assert_eq!(bytes[0], F::from_canonical_u8(0x78));
+        assert_eq!(recomposed, F::from_canonical_u32(0x1234_5678));
+    }
+
+    #[test]
+    /// The recursive constraints build into a circuit, so that this table
+    /// can be verified inside another plonky2 circuit
+    fn test_circuit_constraints() {
+        let stark = RangeCheckU8Stark::<F, D>::new();
+        assert!(test_stark_circuit_constraints::<F, C, _, D>(stark).is_ok());
+    }
+
+    #[test]
+    fn test_column_docs() {
+        assert_eq!(
+            RangeCheckU8Stark::<F, D>::column_docs().len(),
+            RangeCheckU8Stark::<F, D>::COLUMNS
+        );
+    }
+}
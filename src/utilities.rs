@@ -1,16 +1,351 @@
-use plonky2::hash::hash_types::RichField;
-use prettytable::Table;
+use log::{
+    debug,
+    log_enabled,
+    Level,
+};
+use plonky2::{
+    field::{
+        extension::Extendable,
+        polynomial::PolynomialValues,
+        types::Field,
+    },
+    hash::hash_types::RichField,
+};
+use prettytable::{
+    format::{
+        FormatBuilder,
+        LinePosition,
+        LineSeparator,
+    },
+    Table,
+};
+use starky::{
+    constraint_consumer::ConstraintConsumer,
+    evaluation_frame::StarkEvaluationFrame,
+    stark::Stark,
+};
 
-pub fn debug_table<F: RichField, const COLS: usize>(
-    table_name: &str,
+/// Output formats supported by `debug_table`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TableFormat {
+    /// `prettytable`'s default ASCII art table
+    #[default]
+    Ascii,
+    /// GitHub flavoured Markdown, handy for pasting into issues
+    Markdown,
+    /// Comma separated values, handy for spreadsheets
+    Csv,
+}
+
+impl TableFormat {
+    /// Environment variable consulted by `debug_table`. Accepts `ascii`,
+    /// `markdown` (or `md`) and `csv`, anything else falls back to ASCII.
+    pub const ENV_VAR: &'static str = "PIXIE_TABLE_FORMAT";
+
+    pub fn from_env() -> Self {
+        match std::env::var(Self::ENV_VAR)
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "markdown" | "md" => Self::Markdown,
+            "csv" => Self::Csv,
+            _ => Self::Ascii,
+        }
+    }
+}
+
+/// Renders the trace `values` under `headings` as a string in `format`
+pub fn render_table<F: RichField, const COLS: usize>(
     headings: [&str; COLS],
     values: &Vec<[F; COLS]>,
-) {
+    format: TableFormat,
+) -> String {
     let mut table = Table::new();
-    table.add_row(headings.into());
+    match format {
+        TableFormat::Ascii => {
+            table.add_row(headings.into());
+        }
+        TableFormat::Markdown | TableFormat::Csv => {
+            table.set_titles(headings.into());
+        }
+    }
     for row in values {
         table.add_row(row.into());
     }
-    println!("TRACE OUTPUT: {}\n", table_name);
-    table.printstd();
+
+    match format {
+        TableFormat::Ascii => table.to_string(),
+        TableFormat::Markdown => {
+            table.set_format(
+                FormatBuilder::new()
+                    .column_separator('|')
+                    .borders('|')
+                    .separator(
+                        LinePosition::Title,
+                        LineSeparator::new('-', '|', '|', '|'),
+                    )
+                    .padding(1, 1)
+                    .build(),
+            );
+            table.to_string()
+        }
+        TableFormat::Csv => {
+            let writer = table
+                .to_csv(Vec::new())
+                .expect("writing csv to memory should not fail");
+            let bytes = writer
+                .into_inner()
+                .expect("flushing csv to memory should not fail");
+            String::from_utf8(bytes).expect("csv output should be utf-8")
+        }
+    }
+}
+
+/// Logs the trace at debug level, in the format picked by
+/// `TableFormat::from_env`. Silent, and not even rendered, unless debug
+/// logging is enabled.
+pub fn debug_table<F: RichField, const COLS: usize>(
+    table_name: &str,
+    headings: [&str; COLS],
+    values: &Vec<[F; COLS]>,
+) {
+    debug_table_with_format(
+        table_name,
+        headings,
+        values,
+        TableFormat::from_env(),
+    );
+}
+
+pub fn debug_table_with_format<F: RichField, const COLS: usize>(
+    table_name: &str,
+    headings: [&str; COLS],
+    values: &Vec<[F; COLS]>,
+    format: TableFormat,
+) {
+    if !log_enabled!(Level::Debug) {
+        return;
+    }
+    debug!(
+        "TRACE OUTPUT: {}\n\n{}",
+        table_name,
+        render_table(headings, values, format)
+    );
+}
+
+/// Most constraints a stark may have for [check_constraints] to tell them
+/// apart
+pub const MAX_CHECKED_CONSTRAINTS: usize = 512;
+
+/// Evaluates the constraints of `stark` on every pair of adjacent rows of
+/// `trace`, the last row being followed by the first as when proving.
+/// Returns `(row, constraint)` of the first violation, `constraint` being
+/// the index of the violated constraint in the order `eval_packed_generic`
+/// yields them. Cross-table lookups are not checked.
+///
+/// Meant for development: unlike a failing proof, this points at what
+/// broke. Only works for starks with at most [MAX_CHECKED_CONSTRAINTS]
+/// constraints.
+pub fn check_constraints<F, S, const D: usize>(
+    stark: &S,
+    trace: &[PolynomialValues<F>],
+    public_inputs: &[F],
+) -> Option<(usize, usize)>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let rows = trace
+        .first()
+        .map_or(0, |column| column.len());
+    let row_values = |row: usize| {
+        trace
+            .iter()
+            .map(|column| column.values[row])
+            .collect::<Vec<F>>()
+    };
+
+    for row in 0..rows {
+        let is_first = F::from_bool(row == 0);
+        let is_last = F::from_bool(row == rows - 1);
+        // Vanishes on the last row only, where transitions are not checked
+        let z_last = F::ONE - is_last;
+        let coefficients = constraint_polynomial(
+            stark,
+            &row_values(row),
+            &row_values((row + 1) % rows),
+            public_inputs,
+            [z_last, is_first, is_last],
+        );
+        if let Some(degree) = highest_nonzero(&coefficients) {
+            let count = constraint_count(stark, public_inputs.len());
+            return Some((row, count.max(degree + 1) - 1 - degree));
+        }
+    }
+    None
+}
+
+/// The constraint consumer folds the constraints `c_0, ..., c_{n-1}` into
+/// `c_0 * alpha^(n-1) + ... + c_{n-1}` for each of its `alpha`s. Folding
+/// at enough `alpha`s recovers that polynomial, whose coefficient of
+/// `alpha^j` is `c_{n-1-j}`. Returns its coefficients, lowest degree first.
+fn constraint_polynomial<F, S, const D: usize>(
+    stark: &S,
+    local_values: &[F],
+    next_values: &[F],
+    public_inputs: &[F],
+    [z_last, lagrange_first, lagrange_last]: [F; 3],
+) -> Vec<F>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let alphas = (0..MAX_CHECKED_CONSTRAINTS)
+        .map(F::from_canonical_usize)
+        .collect::<Vec<F>>();
+    let mut consumer =
+        ConstraintConsumer::new(alphas, z_last, lagrange_first, lagrange_last);
+    let frame = S::EvaluationFrame::<F, F, 1>::from_values(
+        local_values,
+        next_values,
+        public_inputs,
+    );
+    stark.eval_packed_generic::<F, F, 1>(&frame, &mut consumer);
+
+    let evaluations = consumer.accumulators();
+    match evaluations
+        .iter()
+        .all(Field::is_zero)
+    {
+        true => vec![],
+        false => interpolate_at_naturals(&evaluations),
+    }
+}
+
+/// Number of constraints of `stark`, found as one more than the degree of
+/// the constraint polynomial on rows of arbitrary values, where the first
+/// constraint does not vanish
+fn constraint_count<F, S, const D: usize>(
+    stark: &S,
+    public_inputs: usize,
+) -> usize
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let arbitrary = |len: usize, offset: usize| {
+        (0..len)
+            .map(|idx| {
+                F::from_canonical_u64(0x9e37_79b9)
+                    .exp_u64((offset + idx + 1) as u64)
+            })
+            .collect::<Vec<F>>()
+    };
+    let coefficients = constraint_polynomial(
+        stark,
+        &arbitrary(S::COLUMNS, 0),
+        &arbitrary(S::COLUMNS, S::COLUMNS),
+        &arbitrary(public_inputs, 2 * S::COLUMNS),
+        [F::TWO, F::NEG_ONE, F::from_canonical_u8(3)],
+    );
+    highest_nonzero(&coefficients).map_or(0, |degree| degree + 1)
+}
+
+fn highest_nonzero<F: Field>(coefficients: &[F]) -> Option<usize> {
+    coefficients
+        .iter()
+        .rposition(|coefficient| !coefficient.is_zero())
+}
+
+/// Coefficients, lowest degree first, of the polynomial of degree less
+/// than `evaluations.len()` evaluating to `evaluations[x]` at each `x`
+fn interpolate_at_naturals<F: Field>(evaluations: &[F]) -> Vec<F> {
+    let len = evaluations.len();
+
+    // Divided differences of the Newton form. Points are `0, 1, 2, ...`,
+    // so the points `level` apart always differ by `level`.
+    let mut divided = evaluations.to_vec();
+    for level in 1..len {
+        let inverse = F::from_canonical_usize(level).inverse();
+        for idx in (level..len).rev() {
+            divided[idx] = (divided[idx] - divided[idx - 1]) * inverse;
+        }
+    }
+
+    // Horner's rule on the Newton form, multiplying by `(x - point)`
+    let mut coefficients = vec![F::ZERO; len];
+    for (point, &difference) in divided
+        .iter()
+        .enumerate()
+        .rev()
+    {
+        let point = F::from_canonical_usize(point);
+        for degree in (1..len).rev() {
+            coefficients[degree] =
+                coefficients[degree - 1] - coefficients[degree] * point;
+        }
+        coefficients[0] = difference - coefficients[0] * point;
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::{
+        goldilocks_field::GoldilocksField,
+        types::Field,
+    };
+
+    use super::*;
+
+    type F = GoldilocksField;
+
+    fn tiny_trace() -> Vec<[F; 2]> {
+        vec![
+            [F::from_canonical_u8(1), F::from_canonical_u8(2)],
+            [F::from_canonical_u8(3), F::from_canonical_u8(4)],
+        ]
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let rendered =
+            render_table(["a", "b"], &tiny_trace(), TableFormat::Ascii);
+        assert!(rendered.contains("+---+---+"));
+        assert!(rendered.contains("| a | b |"));
+        assert!(rendered.contains("| 3 | 4 |"));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let rendered =
+            render_table(["a", "b"], &tiny_trace(), TableFormat::Markdown);
+        assert!(rendered.contains("|---|---|"));
+        assert!(rendered.contains("| a | b |"));
+        assert!(rendered.contains("| 3 | 4 |"));
+        assert!(!rendered.contains('+'));
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let rendered =
+            render_table(["a", "b"], &tiny_trace(), TableFormat::Csv);
+        assert_eq!(rendered, "a,b\n1,2\n3,4\n");
+    }
+
+    #[test]
+    fn test_default_format_is_ascii() {
+        assert_eq!(TableFormat::default(), TableFormat::Ascii);
+    }
+
+    #[test]
+    /// `3x^2 + 2x + 1`, recovered from its values at `0, 1, 2, 3`
+    fn test_interpolate_at_naturals() {
+        let evaluations = [1, 6, 17, 34].map(F::from_canonical_u8);
+        assert_eq!(
+            interpolate_at_naturals(&evaluations),
+            [1, 2, 3, 0].map(F::from_canonical_u8)
+        );
+    }
 }
@@ -0,0 +1,15 @@
+/// Implements `get_number_of_columns` for a `#[repr(C)]` struct laying
+/// out the columns of a table, generic over the type of a cell. Each of
+/// its fields is one column, so with `u8` cells its size is the number of
+/// columns.
+macro_rules! derive_get_number_of_columns {
+    ($structure:ident) => {
+        impl<T> $structure<T> {
+            pub const fn get_number_of_columns() -> usize {
+                std::mem::size_of::<$structure<u8>>()
+            }
+        }
+    };
+}
+
+pub(crate) use derive_get_number_of_columns;
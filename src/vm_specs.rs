@@ -1,14 +1,29 @@
 //! This file describes the structures that defines our VM
 
-use std::collections::HashMap;
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    ops::RangeInclusive,
+};
 
+use anyhow::{
+    anyhow,
+    Result,
+};
 use plonky2::hash::hash_types::RichField;
 
+use crate::error::PixieError;
+
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Register {
     #[default]
     R0 = 0,
     R1,
+    R2,
+    R3,
 }
 
 impl From<Register> for usize {
@@ -16,36 +31,125 @@ impl From<Register> for usize {
         match value {
             Register::R0 => 0,
             Register::R1 => 1,
+            Register::R2 => 2,
+            Register::R3 => 3,
+        }
+    }
+}
+
+impl TryFrom<usize> for Register {
+    type Error = anyhow::Error;
+
+    fn try_from(value: usize) -> Result<Self> {
+        match value {
+            0 => Ok(Register::R0),
+            1 => Ok(Register::R1),
+            2 => Ok(Register::R2),
+            3 => Ok(Register::R3),
+            _ => Err(anyhow!("no register with index {}", value)),
         }
     }
 }
 
 pub const REGISTER_COUNT: usize = std::mem::variant_count::<Register>();
 
-/// All memory locations in this VM are addressed via u8.
+/// Short names of the registers, indexed by `usize::from(register)`
+pub const REGISTER_NAMES: [&str; REGISTER_COUNT] = ["r0", "r1", "r2", "r3"];
+
+/// Width of registers, memory cells and memory addresses. A `u32` always
+/// fits in a Goldilocks field element.
+pub type Word = u32;
+
+/// Number of bytes in a [Word]
+pub const WORD_BYTES: usize = (Word::BITS / u8::BITS) as usize;
+
+/// All memory locations in this VM are addressed via [Word].
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
-pub struct MemoryLocation(pub u8);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryLocation(pub Word);
 
 /// All instruction locations in this VM are addresses via u8.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InstructionLocation(pub u8);
 
+/// Weights of `(opcode, operand_a, operand_b)` in the canonical field
+/// encoding of an instruction, see [Instruction::encode_to_field]. The
+/// opcode goes in the high bits, the operands in the low bits. `operand_a`
+/// is a byte and `operand_b` at most a [Word], so no two limbs share a bit
+/// and the encoding is injective.
+pub const INSTRUCTION_ENCODING_WEIGHTS: [u64; 3] = [1 << 40, 1 << 32, 1];
+
+/// Bits taken by [Instruction::encode_to_field], the opcode being a byte
+pub const INSTRUCTION_ENCODING_BITS: u32 = 48;
+
+/// Weights of `(pc, opcode, operand_a, operand_b)` in the field encoding
+/// of an instruction at `pc`, see [Instruction::instruction_data]: the
+/// `pc` byte below [INSTRUCTION_ENCODING_WEIGHTS]. It all takes 56 bits,
+/// well within the field.
+pub const INSTRUCTION_DATA_WEIGHTS: [u64; 4] = [
+    1,
+    INSTRUCTION_ENCODING_WEIGHTS[0] << u8::BITS,
+    INSTRUCTION_ENCODING_WEIGHTS[1] << u8::BITS,
+    INSTRUCTION_ENCODING_WEIGHTS[2] << u8::BITS,
+];
+
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Add(Register, Register),
     Sub(Register, Register),
     Mul(Register, Register),
+    /// Divides the first register by the second, rounding towards zero.
+    /// Dividing by zero is not a valid execution: the simulation errors,
+    /// and there is no trace to prove. The quotient is not constrained by
+    /// the CPU table, so proofs do not attest it, see
+    /// `PixieZKVM::unconstrained_opcodes`.
     Div(Register, Register),
-    Shl(Register, Register),
-    Shr(Register, Register),
+    /// Shifts the register left by the immediate, which is masked to
+    /// `0..Word::BITS`: shifting by `Word::BITS + 3` shifts by `3`
+    Shl(Register, u8),
+    /// Shifts the register right, see [Instruction::Shl]
+    Shr(Register, u8),
     Jz(Register, InstructionLocation),
     Jnz(Register, InstructionLocation),
     Lb(Register, MemoryLocation),
     Sb(Register, MemoryLocation),
     #[default]
     Halt,
+    And(Register, Register),
+    Or(Register, Register),
+    Xor(Register, Register),
+    Not(Register),
+    /// Loads the immediate into the register
+    Li(Register, Word),
+    /// Loads the signed immediate, sign-extended to a [Word], into the
+    /// register. Pairs with [Instruction::Slt], e.g. `-1` loads as
+    /// `0xFFFF_FFFF`. Encoded as its two's-complement byte, see
+    /// [Instruction::get_operands].
+    Lis(Register, i8),
+    /// Copies the second register into the first
+    Mov(Register, Register),
+    /// Sets the first register to `1` if it is less than the second, `0`
+    /// otherwise. Both are read as two's-complement `i32`, i.e. a [Word]
+    /// with its top bit set is negative.
+    Slt(Register, Register),
+    /// Sets the first register to `1` if it equals the second, `0`
+    /// otherwise
+    Seq(Register, Register),
+    /// Appends the register to the output of the execution, see
+    /// `PreflightSimulation::outputs`
+    Out(Register),
+    /// Pushes the location of the next instruction onto the return stack
+    /// and jumps to the subroutine at the location
+    Call(InstructionLocation),
+    /// Pops a location off the return stack and jumps back to it. Errors
+    /// on an empty stack.
+    Ret,
 }
 
+pub const OPCODE_COUNT: usize = std::mem::variant_count::<Instruction>();
+
 impl Instruction {
     /// Not the best of the implementations. But written it like this
     /// for demonstration purposes
@@ -63,25 +167,219 @@ impl Instruction {
             Instruction::Lb(_, _) => 8,
             Instruction::Sb(_, _) => 9,
             Instruction::Halt => 10,
+            Instruction::And(_, _) => 11,
+            Instruction::Or(_, _) => 12,
+            Instruction::Xor(_, _) => 13,
+            Instruction::Not(_) => 14,
+            Instruction::Li(_, _) => 15,
+            Instruction::Lis(_, _) => 16,
+            Instruction::Mov(_, _) => 17,
+            Instruction::Slt(_, _) => 18,
+            Instruction::Seq(_, _) => 19,
+            Instruction::Out(_) => 20,
+            Instruction::Call(_) => 21,
+            Instruction::Ret => 22,
         }
     }
 
+    /// Gas charged for executing the instruction, a rough measure of the
+    /// work it takes. Multiplication and division cost more than the
+    /// other arithmetic, memory accesses more than register moves.
+    pub fn cost(&self) -> u64 {
+        match self {
+            Instruction::Halt => 0,
+            Instruction::Add(_, _)
+            | Instruction::Sub(_, _)
+            | Instruction::Shl(_, _)
+            | Instruction::Shr(_, _)
+            | Instruction::And(_, _)
+            | Instruction::Or(_, _)
+            | Instruction::Xor(_, _)
+            | Instruction::Not(_)
+            | Instruction::Li(_, _)
+            | Instruction::Lis(_, _)
+            | Instruction::Mov(_, _)
+            | Instruction::Slt(_, _)
+            | Instruction::Seq(_, _)
+            | Instruction::Out(_)
+            | Instruction::Jz(_, _)
+            | Instruction::Jnz(_, _)
+            | Instruction::Call(_)
+            | Instruction::Ret => 1,
+            Instruction::Lb(_, _) | Instruction::Sb(_, _) => 2,
+            Instruction::Mul(_, _) => 4,
+            Instruction::Div(_, _) => 8,
+        }
+    }
+
+    /// Operands of the instruction as [Word]s. Registers are encoded
+    /// by their index, locations by their address. Unused operands are `0`.
+    pub fn get_operands(&self) -> [Word; 2] {
+        match self {
+            Instruction::Add(a, b)
+            | Instruction::Sub(a, b)
+            | Instruction::Mul(a, b)
+            | Instruction::Div(a, b)
+            | Instruction::And(a, b)
+            | Instruction::Or(a, b)
+            | Instruction::Xor(a, b)
+            | Instruction::Mov(a, b)
+            | Instruction::Slt(a, b)
+            | Instruction::Seq(a, b) => {
+                [usize::from(*a) as Word, usize::from(*b) as Word]
+            }
+            Instruction::Jz(r, l) | Instruction::Jnz(r, l) => {
+                [usize::from(*r) as Word, Word::from(l.0)]
+            }
+            Instruction::Lb(r, l) | Instruction::Sb(r, l) => {
+                [usize::from(*r) as Word, l.0]
+            }
+            Instruction::Shl(r, amount) | Instruction::Shr(r, amount) => {
+                [usize::from(*r) as Word, Word::from(*amount)]
+            }
+            Instruction::Not(r) | Instruction::Out(r) => {
+                [usize::from(*r) as Word, 0]
+            }
+            Instruction::Call(l) => [0, Word::from(l.0)],
+            Instruction::Li(r, imm) => [usize::from(*r) as Word, *imm],
+            Instruction::Lis(r, imm) => {
+                [usize::from(*r) as Word, Word::from(*imm as u8)]
+            }
+            Instruction::Halt | Instruction::Ret => [0, 0],
+        }
+    }
+
+    /// Inverse of [Self::get_opcode] and [Self::get_operands]. Errors on
+    /// unknown opcodes and on operands no instruction has, including
+    /// unused operands which are not `0`.
+    pub fn from_opcode_and_operands(
+        opcode: u8,
+        [operand_a, operand_b]: [Word; 2],
+    ) -> Result<Self> {
+        let register = |operand: Word| Register::try_from(operand as usize);
+        let byte = |operand: Word| {
+            u8::try_from(operand)
+                .map_err(|_| anyhow!("operand {:#x} is not a byte", operand))
+        };
+        let unused = |operand: Word| match operand {
+            0 => Ok(()),
+            _ => Err(anyhow!("unused operand is {:#x}, not 0", operand)),
+        };
+        let (a, b) = (operand_a, operand_b);
+
+        let instruction = match opcode {
+            0 => Instruction::Add(register(a)?, register(b)?),
+            1 => Instruction::Sub(register(a)?, register(b)?),
+            2 => Instruction::Mul(register(a)?, register(b)?),
+            3 => Instruction::Div(register(a)?, register(b)?),
+            4 => Instruction::Shl(register(a)?, byte(b)?),
+            5 => Instruction::Shr(register(a)?, byte(b)?),
+            6 => Instruction::Jz(register(a)?, InstructionLocation(byte(b)?)),
+            7 => Instruction::Jnz(register(a)?, InstructionLocation(byte(b)?)),
+            8 => Instruction::Lb(register(a)?, MemoryLocation(b)),
+            9 => Instruction::Sb(register(a)?, MemoryLocation(b)),
+            10 => {
+                unused(a)?;
+                unused(b)?;
+                Instruction::Halt
+            }
+            11 => Instruction::And(register(a)?, register(b)?),
+            12 => Instruction::Or(register(a)?, register(b)?),
+            13 => Instruction::Xor(register(a)?, register(b)?),
+            14 => {
+                unused(b)?;
+                Instruction::Not(register(a)?)
+            }
+            15 => Instruction::Li(register(a)?, b),
+            16 => Instruction::Lis(register(a)?, byte(b)? as i8),
+            17 => Instruction::Mov(register(a)?, register(b)?),
+            18 => Instruction::Slt(register(a)?, register(b)?),
+            19 => Instruction::Seq(register(a)?, register(b)?),
+            20 => {
+                unused(b)?;
+                Instruction::Out(register(a)?)
+            }
+            21 => {
+                unused(a)?;
+                Instruction::Call(InstructionLocation(byte(b)?))
+            }
+            22 => {
+                unused(a)?;
+                unused(b)?;
+                Instruction::Ret
+            }
+            _ => return Err(anyhow!("unknown opcode {}", opcode)),
+        };
+        Ok(instruction)
+    }
+
+    /// Canonical encoding of the instruction as a single field element,
+    /// see [INSTRUCTION_ENCODING_WEIGHTS]
+    pub fn encode_to_field<F: RichField>(&self) -> F {
+        let [operand_a, operand_b] = self.get_operands();
+        [u32::from(self.get_opcode()), operand_a, operand_b]
+            .into_iter()
+            .zip(INSTRUCTION_ENCODING_WEIGHTS)
+            .map(|(limb, weight)| {
+                F::from_canonical_u32(limb) * F::from_canonical_u64(weight)
+            })
+            .sum()
+    }
+
+    /// Inverse of [Self::encode_to_field]. Errors on elements which are
+    /// not the encoding of any instruction.
+    pub fn decode_from_field<F: RichField>(f: F) -> Result<Self> {
+        let encoding = f.to_canonical_u64();
+        if encoding >> INSTRUCTION_ENCODING_BITS != 0 {
+            return Err(anyhow!(
+                "{:#x} takes more than {} bits",
+                encoding,
+                INSTRUCTION_ENCODING_BITS
+            ));
+        }
+        // Each limb is what is left below the weight of the limb above it
+        let [opcode_weight, operand_a_weight, operand_b_weight] =
+            INSTRUCTION_ENCODING_WEIGHTS;
+        Self::from_opcode_and_operands(
+            (encoding / opcode_weight) as u8,
+            [
+                (encoding % opcode_weight / operand_a_weight) as Word,
+                (encoding % operand_a_weight / operand_b_weight) as Word,
+            ],
+        )
+    }
+
+    /// Packs the instruction residing at `pc` into a single field element,
+    /// see [INSTRUCTION_DATA_WEIGHTS]. Shared by all tables looking up
+    /// instructions of the program.
+    pub fn instruction_data<F: RichField>(
+        &self,
+        pc: u8,
+    ) -> F {
+        F::from_canonical_u8(pc)
+            + self.encode_to_field::<F>()
+                * F::from_canonical_u64(1 << u8::BITS)
+    }
+
     /// One-hot encoded description of the Opcode
-    pub fn one_hot_encode(&self) -> [u8; 11] {
-        let mut one_hot_enc = [0; 11];
+    pub fn one_hot_encode(&self) -> [u8; OPCODE_COUNT] {
+        let mut one_hot_enc = [0; OPCODE_COUNT];
         one_hot_enc[self.get_opcode() as usize] = 1;
         one_hot_enc
     }
 
-    /// One-hot encodes the opcode and applies a function to it
-    pub fn one_hot_encode_and_apply<F: RichField>(&self) -> [F; 11] {
-        let mut encode_f: [F; 11] = [F::ZERO; 11];
+    /// [Self::one_hot_encode] lifted into `F`: `F::ONE` at index
+    /// [Self::get_opcode], `F::ZERO` everywhere else. Nothing else is
+    /// folded in, these are exactly the opcode columns of a CPU table row.
+    pub fn one_hot_encode_and_apply<F: RichField>(&self) -> [F; OPCODE_COUNT] {
+        let mut encode_f: [F; OPCODE_COUNT] = [F::ZERO; OPCODE_COUNT];
         encode_f[self.get_opcode() as usize] = F::ONE;
         encode_f
     }
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     /// The entrypoint of the program
     pub entry_point: u8,
@@ -90,5 +388,551 @@ pub struct Program {
     pub code: HashMap<u8, Instruction>,
 
     /// Initial memory layout at the start of the program
-    pub memory_init: HashMap<u8, u8>,
+    pub memory_init: HashMap<Word, Word>,
+
+    /// Addresses the program may load from and store to
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub memory_config: MemoryConfig,
+}
+
+/// Addresses a [Program] may access. A load outside of `readable` or a
+/// store outside of `writable` stops the simulation, e.g. keeping a
+/// read-only data segment out of `writable`. Only the simulation checks
+/// these, the tables do not constrain them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryConfig {
+    pub readable: RangeInclusive<Word>,
+    pub writable: RangeInclusive<Word>,
+}
+
+/// The whole address space is readable and writable
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            readable: 0..=Word::MAX,
+            writable: 0..=Word::MAX,
+        }
+    }
+}
+
+impl MemoryConfig {
+    /// `Ok` if `instruction`, about to execute at `pc`, accesses memory it
+    /// is permitted to
+    pub fn check_access(
+        &self,
+        instruction: &Instruction,
+        pc: u8,
+    ) -> Result<(), PixieError> {
+        let (addr, permitted) = match instruction {
+            Instruction::Lb(_, addr) => (addr.0, &self.readable),
+            Instruction::Sb(_, addr) => (addr.0, &self.writable),
+            _ => return Ok(()),
+        };
+        match permitted.contains(&addr) {
+            true => Ok(()),
+            false => Err(PixieError::MemoryAccessViolation { addr, pc }),
+        }
+    }
+}
+
+impl Program {
+    /// Replaces `memory_init` with the flat memory `image`, the byte at
+    /// index `i` being the value at address `i`. Cells holding `0` read as
+    /// `0` either way, so they are only kept if `include_zero_cells` is set.
+    pub fn with_memory_image(
+        mut self,
+        image: [u8; 256],
+        include_zero_cells: bool,
+    ) -> Self {
+        self.memory_init = image
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, value)| include_zero_cells || value != 0)
+            .map(|(addr, value)| (addr as Word, Word::from(value)))
+            .collect();
+        self
+    }
+
+    /// Number of basic blocks in the code. A new block starts at the
+    /// entry point, at every jump target, right after every jump or
+    /// `Halt`, and after any gap in the instruction addresses.
+    pub fn basic_block_count(&self) -> usize {
+        let mut leaders = HashSet::new();
+        if !self
+            .code
+            .is_empty()
+        {
+            leaders.insert(self.entry_point);
+        }
+        for (&pc, instruction) in &self.code {
+            match instruction {
+                Instruction::Jz(_, target)
+                | Instruction::Jnz(_, target)
+                | Instruction::Call(target) => {
+                    leaders.insert(target.0);
+                    leaders.insert(pc.wrapping_add(1));
+                }
+                Instruction::Halt | Instruction::Ret => {
+                    leaders.insert(pc.wrapping_add(1));
+                }
+                _ => {}
+            }
+            if pc == 0
+                || !self
+                    .code
+                    .contains_key(&(pc - 1))
+            {
+                leaders.insert(pc);
+            }
+        }
+        leaders
+            .into_iter()
+            .filter(|pc| {
+                self.code
+                    .contains_key(pc)
+            })
+            .count()
+    }
+
+    /// Whether some path of execution from the entry point reaches a
+    /// `Halt`. Both ways out of a conditional jump are followed, whatever
+    /// the register values, so this may hold for programs which loop
+    /// forever on their actual inputs.
+    pub fn halt_reachable(&self) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.entry_point];
+        while let Some(pc) = pending.pop() {
+            if !visited.insert(pc) {
+                continue;
+            }
+            // Running off the code ends the path without halting
+            let Some(instruction) = self
+                .code
+                .get(&pc)
+            else {
+                continue;
+            };
+            match instruction {
+                Instruction::Halt => return true,
+                // Returns land right after some `Call`, which is followed
+                // from the `Call` itself
+                Instruction::Ret => continue,
+                Instruction::Jz(_, target)
+                | Instruction::Jnz(_, target)
+                | Instruction::Call(target) => {
+                    pending.push(target.0);
+                }
+                _ => {}
+            }
+            pending.extend(pc.checked_add(1));
+        }
+        false
+    }
+
+    /// Checks, without running it, that the program can be simulated: the
+    /// entry point holds an instruction, every jump and call targets one
+    /// and some `Halt` is reachable, see [Self::halt_reachable]. Execution
+    /// may still fail, e.g. by running off the code or dividing by zero.
+    pub fn validate(&self) -> Result<(), PixieError> {
+        if !self
+            .code
+            .contains_key(&self.entry_point)
+        {
+            return Err(PixieError::EntryPointMissing);
+        }
+        let mut pcs = self
+            .code
+            .keys()
+            .copied()
+            .collect::<Vec<u8>>();
+        pcs.sort();
+        for pc in pcs {
+            if let Instruction::Jz(_, target)
+            | Instruction::Jnz(_, target)
+            | Instruction::Call(target) = self.code[&pc]
+            {
+                if !self
+                    .code
+                    .contains_key(&target.0)
+                {
+                    return Err(PixieError::JumpTargetMissing {
+                        pc,
+                        target: target.0,
+                    });
+                }
+            }
+        }
+        if !self.halt_reachable() {
+            return Err(PixieError::HaltUnreachable);
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [Program], laying instructions out one after the other from
+/// program counter `0`
+///
+/// The setters are named after what they fill in: [Self::instruction]
+/// appends to the code, [Self::entry_point] and [Self::memory] set the
+/// entry point and the initial memory. There are deliberately no `push`,
+/// `entry` or `data` aliases, so each field has exactly one setter.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramBuilder {
+    entry_point: u8,
+    instructions: Vec<Instruction>,
+    memory_init: HashMap<Word, Word>,
+    memory_config: MemoryConfig,
+    strict: bool,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes [Self::build] reject programs which cannot reach a `Halt`,
+    /// see [Program::halt_reachable]
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    pub fn entry_point(
+        mut self,
+        pc: u8,
+    ) -> Self {
+        self.entry_point = pc;
+        self
+    }
+
+    /// Appends `instruction` to the code
+    pub fn instruction(
+        mut self,
+        instruction: Instruction,
+    ) -> Self {
+        self.instructions
+            .push(instruction);
+        self
+    }
+
+    /// Initializes memory at `address` to `value`
+    pub fn memory(
+        mut self,
+        address: Word,
+        value: Word,
+    ) -> Self {
+        self.memory_init
+            .insert(address, value);
+        self
+    }
+
+    /// Restricts the addresses the program may access, see [MemoryConfig]
+    pub fn memory_config(
+        mut self,
+        memory_config: MemoryConfig,
+    ) -> Self {
+        self.memory_config = memory_config;
+        self
+    }
+
+    pub fn build(self) -> Result<Program> {
+        if self
+            .instructions
+            .len()
+            > 1 << u8::BITS
+        {
+            return Err(anyhow!(
+                "program exceeds {} instructions",
+                1 << u8::BITS
+            ));
+        }
+
+        let program = Program {
+            entry_point: self.entry_point,
+            code: self
+                .instructions
+                .into_iter()
+                .enumerate()
+                .map(|(idx, inst)| (idx as u8, inst))
+                .collect(),
+            memory_init: self.memory_init,
+            memory_config: self.memory_config,
+        };
+
+        if self.strict && !program.halt_reachable() {
+            return Err(anyhow!(
+                "no `Halt` is reachable from the entry point {}",
+                program.entry_point
+            ));
+        }
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_with_memory_image() {
+        let mut image = [0; 256];
+        image[0x00] = 0x01;
+        image[0x40] = 0x20;
+        image[0xff] = 0x45;
+
+        let program = fixtures::add().with_memory_image(image, false);
+        assert_eq!(
+            program.memory_init,
+            HashMap::from_iter(vec![(0x00, 0x01), (0x40, 0x20), (0xff, 0x45)])
+        );
+
+        let program = fixtures::add().with_memory_image(image, true);
+        assert_eq!(
+            program
+                .memory_init
+                .len(),
+            256
+        );
+        assert_eq!(program.memory_init[&0x41], 0);
+    }
+
+    #[test]
+    fn test_basic_block_count() {
+        assert_eq!(Program::default().basic_block_count(), 0);
+        // Straight line code
+        assert_eq!(fixtures::add().basic_block_count(), 1);
+        // `0..=1` setup, `2..=4` loop body ending in `Jnz`, `5` halting
+        assert_eq!(fixtures::simple_loop().basic_block_count(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    /// Programs survive a round trip through JSON, and simulate the same
+    fn test_serde_json_round_trip() {
+        use crate::preflight_simulator::PreflightSimulation;
+
+        let program = fixtures::add();
+        let json = serde_json::to_string(&program).unwrap();
+        let deserialized: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, program);
+
+        let final_memory = |program: &Program| {
+            PreflightSimulation::simulate(program)
+                .unwrap()
+                .trace_rows
+                .last()
+                .unwrap()
+                .memory_snapshot
+                .clone()
+        };
+        assert_eq!(final_memory(&deserialized), final_memory(&program));
+    }
+
+    /// One instruction of every variant, in opcode order
+    fn every_variant() -> Vec<Instruction> {
+        let (reg, mem, inst) =
+            (Register::R1, MemoryLocation(0x40), InstructionLocation(0x02));
+        vec![
+            Instruction::Add(reg, reg),
+            Instruction::Sub(reg, reg),
+            Instruction::Mul(reg, reg),
+            Instruction::Div(reg, reg),
+            Instruction::Shl(reg, 3),
+            Instruction::Shr(reg, 3),
+            Instruction::Jz(reg, inst),
+            Instruction::Jnz(reg, inst),
+            Instruction::Lb(reg, mem),
+            Instruction::Sb(reg, mem),
+            Instruction::Halt,
+            Instruction::And(reg, reg),
+            Instruction::Or(reg, reg),
+            Instruction::Xor(reg, reg),
+            Instruction::Not(reg),
+            Instruction::Li(reg, 0x2a),
+            Instruction::Lis(reg, -1),
+            Instruction::Mov(reg, Register::R2),
+            Instruction::Slt(reg, Register::R2),
+            Instruction::Seq(reg, Register::R2),
+            Instruction::Out(reg),
+            Instruction::Call(inst),
+            Instruction::Ret,
+        ]
+    }
+
+    #[test]
+    /// The field one-hot encoding has a single `F::ONE`, at the opcode
+    fn test_one_hot_encode_and_apply() {
+        use plonky2::field::{
+            goldilocks_field::GoldilocksField as F,
+            types::Field,
+        };
+
+        let instructions = every_variant();
+        assert_eq!(instructions.len(), OPCODE_COUNT);
+        for (opcode, instruction) in instructions
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(usize::from(instruction.get_opcode()), opcode);
+
+            let encoding = instruction.one_hot_encode_and_apply::<F>();
+            assert_eq!(
+                encoding
+                    .iter()
+                    .copied()
+                    .sum::<F>(),
+                F::ONE
+            );
+            assert_eq!(encoding[opcode], F::ONE);
+            assert_eq!(
+                encoding,
+                instruction
+                    .one_hot_encode()
+                    .map(F::from_canonical_u8)
+            );
+        }
+    }
+
+    #[test]
+    /// Every instruction is rebuilt from its opcode and operands, and
+    /// nothing else is accepted
+    fn test_from_opcode_and_operands() {
+        for instruction in every_variant() {
+            assert_eq!(
+                Instruction::from_opcode_and_operands(
+                    instruction.get_opcode(),
+                    instruction.get_operands(),
+                )
+                .unwrap(),
+                instruction
+            );
+        }
+        let decode = Instruction::from_opcode_and_operands;
+        assert!(decode(OPCODE_COUNT as u8, [0, 0]).is_err());
+        assert!(decode(0, [REGISTER_COUNT as Word, 0]).is_err());
+        assert!(decode(6, [0, 0x100]).is_err());
+        assert!(decode(10, [0, 1]).is_err());
+    }
+
+    #[test]
+    /// Every instruction decodes back from its field encoding, and
+    /// elements encoding no instruction are rejected
+    fn test_encode_to_field() {
+        use plonky2::field::{
+            goldilocks_field::GoldilocksField as F,
+            types::Field,
+        };
+
+        let encodings = every_variant()
+            .into_iter()
+            .map(|instruction| {
+                let encoding = instruction.encode_to_field::<F>();
+                assert_eq!(
+                    Instruction::decode_from_field(encoding).unwrap(),
+                    instruction
+                );
+                encoding
+            })
+            .collect::<HashSet<F>>();
+        assert_eq!(encodings.len(), OPCODE_COUNT);
+
+        // Opcode in the high bits, operands in the low bits
+        assert_eq!(
+            Instruction::Sb(Register::R2, MemoryLocation(0xdeadbeef))
+                .encode_to_field::<F>(),
+            F::from_canonical_u64(0x09_02_deadbeef)
+        );
+        let decode = Instruction::decode_from_field::<F>;
+        assert!(decode(F::from_canonical_u64(1 << 48)).is_err());
+        assert!(decode(F::from_canonical_u64(0xff_00_00000000)).is_err());
+        assert!(decode(F::NEG_ONE).is_err());
+        // `Halt` with a nonzero operand
+        assert!(decode(F::from_canonical_u64(0x0a_00_00000001)).is_err());
+    }
+
+    #[test]
+    fn test_halt_reachable() {
+        assert!(fixtures::add().halt_reachable());
+        assert!(fixtures::simple_loop().halt_reachable());
+        assert!(!Program::default().halt_reachable());
+    }
+
+    #[test]
+    /// Strict builds fail without a reachable `Halt`, others go through
+    fn test_strict_builder() {
+        // Loops over `1..=2` forever, the `Halt` before it is never reached
+        let builder = ProgramBuilder::new()
+            .instruction(Instruction::Halt)
+            .instruction(Instruction::Li(Register::R0, 1))
+            .instruction(Instruction::Jnz(Register::R0, InstructionLocation(1)))
+            .entry_point(1);
+        assert!(builder
+            .clone()
+            .strict()
+            .build()
+            .is_err());
+        assert_eq!(
+            builder
+                .build()
+                .unwrap()
+                .code
+                .len(),
+            3
+        );
+
+        // The `Halt` is only reached when the branch is not taken
+        let program = ProgramBuilder::new()
+            .strict()
+            .memory(0x40, 0x01)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Jnz(Register::R0, InstructionLocation(0)))
+            .instruction(Instruction::Halt)
+            .build()
+            .unwrap();
+        assert_eq!(program.memory_init[&0x40], 0x01);
+    }
+
+    #[test]
+    /// Instructions land at consecutive program counters from `0`, in the
+    /// order they were added
+    fn test_builder_layout() {
+        let program = ProgramBuilder::new()
+            .entry_point(1)
+            .memory(0x40, 0x20)
+            .memory(0x41, 0x45)
+            .instruction(Instruction::Halt)
+            .instruction(Instruction::Lb(Register::R0, MemoryLocation(0x40)))
+            .instruction(Instruction::Jz(Register::R0, InstructionLocation(0)))
+            .build()
+            .unwrap();
+        assert_eq!(
+            program,
+            Program {
+                entry_point: 1,
+                code: HashMap::from([
+                    (0, Instruction::Halt),
+                    (1, Instruction::Lb(Register::R0, MemoryLocation(0x40))),
+                    (2, Instruction::Jz(Register::R0, InstructionLocation(0))),
+                ]),
+                memory_init: HashMap::from([(0x40, 0x20), (0x41, 0x45)]),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            ProgramBuilder::new()
+                .build()
+                .unwrap(),
+            Program::default()
+        );
+        let too_long = (0..=u8::MAX as usize + 1).fold(
+            ProgramBuilder::new(),
+            |builder, _| builder.instruction(Instruction::Halt),
+        );
+        assert!(too_long
+            .build()
+            .is_err());
+    }
 }
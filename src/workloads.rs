@@ -0,0 +1,235 @@
+//! Parameterized programs for benchmarking, along with a harness timing
+//! the stages from a [Program] to the traces of the tables. Everything
+//! here is public so that `benches/` can get at it.
+
+use log::Level;
+use plonky2::{
+    field::{
+        goldilocks_field::GoldilocksField,
+        polynomial::PolynomialValues,
+    },
+    timed,
+    util::timing::TimingTree,
+};
+
+use crate::{
+    error::PixieError,
+    preflight_simulator::PreflightSimulation,
+    stark_cpu::CPUStark,
+    stark_pixie_zkvm::generate_traces,
+    vm_specs::{
+        Instruction,
+        InstructionLocation,
+        MemoryLocation,
+        Program,
+        ProgramBuilder,
+        Register,
+    },
+};
+
+/// Field the harness generates traces over
+pub type F = GoldilocksField;
+pub const D: usize = 2;
+
+/// Rows [arithmetic_loop] executes in, outside of the loop body
+const ARITHMETIC_LOOP_OVERHEAD: usize = 5;
+/// Rows of one round of the loop of [arithmetic_loop]
+const ARITHMETIC_LOOP_BODY: usize = 5;
+
+/// Most cells [memory_churn] can go through before its code no longer
+/// fits the `u8` program counter
+pub const MAX_CHURN_CELLS: u8 = 84;
+
+/// Mixes `R3` with an add, a multiply and a xor, `iterations` times.
+/// Executes in exactly `5 * iterations + 5` rows, see
+/// [arithmetic_loop_rows].
+pub fn arithmetic_loop(iterations: u32) -> Program {
+    ProgramBuilder::new()
+        .strict()
+        .instruction(Instruction::Li(Register::R0, iterations))
+        .instruction(Instruction::Li(Register::R1, 1))
+        .instruction(Instruction::Li(Register::R2, 3))
+        .instruction(Instruction::Jz(Register::R0, InstructionLocation(9)))
+        // Loop body
+        .instruction(Instruction::Add(Register::R3, Register::R2))
+        .instruction(Instruction::Mul(Register::R3, Register::R2))
+        .instruction(Instruction::Xor(Register::R3, Register::R0))
+        .instruction(Instruction::Sub(Register::R0, Register::R1))
+        .instruction(Instruction::Jnz(Register::R0, InstructionLocation(4)))
+        .instruction(Instruction::Halt)
+        .build()
+        .expect("arithmetic loop is a valid program")
+}
+
+/// Number of rows the execution of [arithmetic_loop] takes
+pub fn arithmetic_loop_rows(iterations: u32) -> usize {
+    ARITHMETIC_LOOP_OVERHEAD + ARITHMETIC_LOOP_BODY * iterations as usize
+}
+
+/// Most iterations of [arithmetic_loop] executing in at most `rows` rows,
+/// i.e. whose CPU trace pads to `rows` when that is a power of two
+pub fn iterations_for_rows(rows: usize) -> u32 {
+    (rows.saturating_sub(ARITHMETIC_LOOP_OVERHEAD) / ARITHMETIC_LOOP_BODY)
+        as u32
+}
+
+/// Stores a distinct value to each of `cells` addresses, then loads them
+/// all back. Executes in exactly `3 * cells + 3` rows. Code cannot loop
+/// over addresses, so it is unrolled, which is why `cells` is at most
+/// [MAX_CHURN_CELLS].
+///
+/// Panics if `cells` exceeds [MAX_CHURN_CELLS]
+pub fn memory_churn(cells: u8) -> Program {
+    assert!(
+        cells <= MAX_CHURN_CELLS,
+        "at most {} cells can be churned",
+        MAX_CHURN_CELLS
+    );
+    let mut builder = ProgramBuilder::new()
+        .strict()
+        .instruction(Instruction::Li(Register::R0, 1))
+        .instruction(Instruction::Li(Register::R1, 1));
+    for cell in 0..cells {
+        builder = builder
+            .instruction(Instruction::Sb(
+                Register::R0,
+                MemoryLocation(u32::from(cell)),
+            ))
+            .instruction(Instruction::Add(Register::R0, Register::R1));
+    }
+    for cell in 0..cells {
+        builder = builder.instruction(Instruction::Lb(
+            Register::R2,
+            MemoryLocation(u32::from(cell)),
+        ));
+    }
+    builder
+        .instruction(Instruction::Halt)
+        .build()
+        .expect("memory churn is a valid program")
+}
+
+/// Simulates `prog`, allowing `max_cycles` CPU cycles rather than
+/// [PreflightSimulation::MAX_CPU_CYCLES_ALLOWED], which large workloads
+/// exceed
+pub fn simulate(
+    prog: &Program,
+    max_cycles: usize,
+) -> Result<PreflightSimulation, PixieError> {
+    PreflightSimulation::simulate_with_budget(prog, max_cycles)
+}
+
+/// Simulates `prog` within `max_cycles` CPU cycles, then generates the
+/// traces of all the tables, timing both. Print the result with
+/// [TimingTree::print]. Run with debug logging disabled: the tables are
+/// rendered to the log otherwise, see `utilities::debug_table`, which
+/// takes far longer than generating them.
+pub fn time_trace_generation(
+    prog: &Program,
+    max_cycles: usize,
+) -> Result<TimingTree, PixieError> {
+    let mut timing = TimingTree::new("trace generation", Level::Info);
+    let simulation = timed!(
+        timing,
+        Level::Info,
+        "simulate",
+        simulate(prog, max_cycles)?
+    );
+    timed!(
+        timing,
+        Level::Info,
+        "generate CPU trace",
+        cpu_trace(&simulation)
+    );
+    timed!(
+        timing,
+        Level::Info,
+        "generate all traces",
+        generate_traces::<F, D>(prog, &simulation)
+    );
+    Ok(timing)
+}
+
+/// Trace of the CPU table, the piece of trace generation benchmarked on
+/// its own. Like [time_trace_generation], only meaningful with debug
+/// logging disabled.
+pub fn cpu_trace(simulation: &PreflightSimulation) -> Vec<PolynomialValues<F>> {
+    CPUStark::<F, D>::generate_trace(simulation)
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use super::*;
+    use crate::stark_pixie_zkvm::{
+        generate_proof,
+        verify_proof,
+    };
+
+    type C = PoseidonGoldilocksConfig;
+
+    /// Simulates `prog` in exactly `rows` rows, then proves and verifies
+    /// its execution
+    fn assert_verifiable(
+        prog: &Program,
+        rows: usize,
+    ) {
+        let simulation =
+            PreflightSimulation::simulate_with_budget(prog, rows).unwrap();
+        assert_eq!(simulation.trace_rows.len(), rows);
+
+        let proof = generate_proof::<F, C, D>(prog).unwrap();
+        assert!(verify_proof(&proof, prog).is_ok());
+    }
+
+    #[test]
+    fn test_arithmetic_loop() {
+        for iterations in [0, 1, 7] {
+            assert_verifiable(
+                &arithmetic_loop(iterations),
+                arithmetic_loop_rows(iterations),
+            );
+        }
+        assert_eq!(iterations_for_rows(1 << 10), 203);
+        assert!(arithmetic_loop_rows(iterations_for_rows(1 << 10)) <= 1 << 10);
+    }
+
+    #[test]
+    fn test_memory_churn() {
+        for cells in [0, 1, 16] {
+            assert_verifiable(&memory_churn(cells), 3 * cells as usize + 3);
+        }
+        let simulation =
+            PreflightSimulation::simulate(&memory_churn(16)).unwrap();
+        let last_row = simulation
+            .trace_rows
+            .last()
+            .unwrap();
+        assert_eq!(last_row.memory_snapshot[&15], 16);
+        assert_eq!(last_row.get_registers()[2], 16);
+    }
+
+    #[test]
+    /// The largest churn still builds
+    fn test_max_churn_cells() {
+        let program = memory_churn(MAX_CHURN_CELLS);
+        let simulation = PreflightSimulation::simulate(&program).unwrap();
+        assert_eq!(
+            simulation.cycle_count(),
+            3 * u32::from(MAX_CHURN_CELLS) + 3
+        );
+    }
+
+    #[test]
+    /// The harness errors rather than runs past the cycle budget
+    fn test_time_trace_generation() {
+        let program = arithmetic_loop(10);
+        let rows = arithmetic_loop_rows(10);
+        assert!(time_trace_generation(&program, rows).is_ok());
+        assert_eq!(
+            time_trace_generation(&program, 20).unwrap_err(),
+            PixieError::CycleBudgetExceeded { budget: 20 }
+        );
+    }
+}